@@ -1,21 +1,27 @@
 use {
     ratatui::{
         crossterm::{
-            event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+            event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
             execute,
             terminal::{
                 disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
             },
+            tty::IsTty,
         },
         prelude::*,
         symbols::border,
         widgets::{
             block::{Position, Title},
-            Block, HighlightSpacing, List, ListState, Padding, Paragraph, Wrap,
+            Block, HighlightSpacing, List, ListItem, ListState, Padding, Paragraph, Wrap,
         },
         Terminal,
     },
-    std::{collections::BTreeSet, io},
+    std::{
+        borrow::Cow,
+        collections::{BTreeMap, BTreeSet},
+        fmt,
+        io::{self, Write},
+    },
     tap::Tap,
 };
 
@@ -25,9 +31,112 @@ use {
 pub struct ListEntry<K> {
     pub key: K,
     pub name: String,
+    /// Short labels displayed discreetly after the name.
+    pub badges: Vec<String>,
+    /// Tags displayed after the name, which can be searched with `#tag`.
+    pub tags: Vec<String>,
+    /// Description, searched along with the name.
+    pub description: Option<String>,
+    /// Longer text which is only searched with `~text`, such as the script of
+    /// a command.
+    pub details: Option<String>,
+    /// Where the entry comes from, such as a file name, which is only
+    /// searched with `@origin`.
+    pub origin: Option<String>,
+    /// Key selecting the entry immediately when pressed while the search
+    /// input is empty.
+    pub hotkey: Option<char>,
+    /// Short text displayed discreetly at the end of the line, such as when
+    /// the entry was last used.
+    pub note: Option<String>,
+    /// Entries with a higher priority are displayed first in search results,
+    /// entries with the same priority are sorted by name.
+    pub priority: i32,
+}
+
+impl<K> ListEntry<K> {
+    /// Entry with only a key and a name, other fields can then be set using
+    /// struct update syntax.
+    pub fn new(key: K, name: impl Into<String>) -> Self {
+        Self {
+            key,
+            name: name.into(),
+            badges: Vec::new(),
+            tags: Vec::new(),
+            description: None,
+            details: None,
+            origin: None,
+            hotkey: None,
+            note: None,
+            priority: 0,
+        }
+    }
+
+    /// Check if the entry matches a search term normalized with
+    /// [`normalize`]. A term matches if it is contained in the name, a tag or
+    /// the description, while terms starting with `#` only match tags, terms
+    /// starting with `~` only match the details and terms starting with `@`
+    /// only match the origin.
+    pub fn matches(&self, term: &str) -> bool {
+        let (kind, text) = TermKind::split_prefix(term);
+        self.matches_text(kind, text)
+    }
+
+    /// Check if the normalized `text` is contained in the fields searched by
+    /// terms of this kind.
+    fn matches_text(&self, kind: TermKind, text: &str) -> bool {
+        let contains = |field: &str| normalize(field).contains(text);
+
+        match kind {
+            TermKind::Tag => self.tags.iter().any(|tag| contains(tag)),
+            TermKind::Details => self.details.as_deref().is_some_and(contains),
+            TermKind::Origin => self.origin.as_deref().is_some_and(contains),
+            TermKind::Any => {
+                contains(&self.name)
+                    || self.tags.iter().any(|tag| contains(tag))
+                    || self.description.as_deref().is_some_and(contains)
+            }
+        }
+    }
+}
+
+/// Fields of the entries searched by a term, depending on its prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TermKind {
+    /// No prefix: name, tags and description.
+    Any,
+    /// `#`: tags.
+    Tag,
+    /// `~`: details.
+    Details,
+    /// `@`: origin.
+    Origin,
+}
+
+impl TermKind {
+    /// Kind of the term according to its prefix, and the rest of the term.
+    fn split_prefix(term: &str) -> (Self, &str) {
+        let kind = match term.chars().next() {
+            Some('#') => Self::Tag,
+            Some('~') => Self::Details,
+            Some('@') => Self::Origin,
+            _ => return (Self::Any, term),
+        };
+        (kind, &term[1..])
+    }
+}
+
+/// Term of a search input, with its prefixes parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SearchTerm {
+    /// Starts with `!`, entries matching the term are excluded.
+    negated: bool,
+    kind: TermKind,
+    /// Searched text, without the prefixes and quotes.
+    text: String,
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct ListSearchExtra<'k, K> {
     /// Title of the box.
     pub title: String,
@@ -37,23 +146,167 @@ pub struct ListSearchExtra<'k, K> {
     pub multi_select: bool,
     /// List showed if the search input is empty (history for iforgor).
     pub empty_search_list: Option<&'k [ListEntry<K>]>,
+    /// Filter hiding some entries, for checks too slow to be performed for all
+    /// entries on each key press.
+    pub entry_filter: Option<EntryFilter<'k, K>>,
+    /// Content of the search input when starting.
+    pub initial_search: String,
+    /// Secondary actions which can be triggered instead of confirming with
+    /// Enter.
+    pub actions: Vec<Action>,
+    /// Display the description of entries on a second line.
+    pub show_descriptions: bool,
+    /// In single-select mode, let the user queue entries with Tab before
+    /// confirming (see [`ListSearchOutcome::queued`]).
+    pub queue: bool,
+    /// Let the user reload the items with Ctrl+R.
+    pub reload: Option<Reload<'k, K>>,
+    /// Remove the empty rows around the list and hide `text`, to display more
+    /// entries in small terminals.
+    pub compact: bool,
+    /// Symbol displayed before the highlighted entry.
+    pub highlight_symbol: String,
+    /// Style of the highlighted entry.
+    pub highlight_style: Style,
+    /// Display the number of items followed by this label (such as
+    /// `commands`) in the top left corner, along with the number of entries of
+    /// `empty_search_list` (as recent ones).
+    pub count_label: Option<String>,
+    /// When to use colors.
+    pub color: ColorChoice,
+}
+
+/// Secondary action triggered by pressing Ctrl and a key. It applies to the
+/// highlighted entry, or to the selected entries in multi-select mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Action {
+    /// Key pressed with Ctrl to trigger the action, in lowercase.
+    pub key: char,
+    /// Name displayed in the instructions.
+    pub name: String,
+}
+
+// Not derived as it would require `K: Default`.
+impl<'k, K> Default for ListSearchExtra<'k, K> {
+    fn default() -> Self {
+        Self {
+            title: String::new(),
+            text: String::new(),
+            multi_select: false,
+            empty_search_list: None,
+            entry_filter: None,
+            initial_search: String::new(),
+            actions: Vec::new(),
+            show_descriptions: false,
+            queue: false,
+            reload: None,
+            compact: false,
+            highlight_symbol: "> ".to_string(),
+            highlight_style: Style::new().bold().blue(),
+            count_label: None,
+            color: ColorChoice::Auto,
+        }
+    }
+}
+
+/// When to use colors. Text styles such as bold are kept without colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Use colors if stderr (where the list is displayed) is a terminal,
+    /// unless the `NO_COLOR` environment variable is set.
+    #[default]
+    Auto,
+    /// Always use colors.
+    Always,
+    /// Never use colors.
+    Never,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            Self::Auto => {
+                io::stderr().is_tty()
+                    && std::env::var_os("NO_COLOR").is_none_or(|var| var.is_empty())
+            }
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+/// Result of [`ListSearch::run_detailed`].
+#[derive(Debug, Clone)]
+pub struct ListSearchOutcome<K> {
+    /// Keys of the selected items, empty if the user quit.
+    pub selected: BTreeSet<K>,
+    /// Selected entries, in the same order as `selected`.
+    pub entries: Vec<ListEntry<K>>,
+    /// Entries queued with Tab before confirming, in order. The confirmed
+    /// entry (if any) is in `entries`, and should be handled after them.
+    pub queued: Vec<ListEntry<K>>,
+    /// Content of the search input when the user confirmed or quit.
+    pub search: String,
+    /// Key of the triggered [`Action`], `None` if the selection was confirmed
+    /// with Enter.
+    pub action: Option<char>,
+}
+
+/// Decides if an entry can be displayed.
+///
+/// It is only called on entries matching the search input, and at most once
+/// per entry for the duration of [`ListSearch::run`].
+pub struct EntryFilter<'k, K>(pub &'k dyn Fn(&ListEntry<K>) -> bool);
+
+impl<'k, K> Clone for EntryFilter<'k, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'k, K> Copy for EntryFilter<'k, K> {}
+
+impl<'k, K> fmt::Debug for EntryFilter<'k, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EntryFilter").finish_non_exhaustive()
+    }
+}
+
+/// Provides a new list of items when the user presses Ctrl+R. The entry
+/// filter is evaluated again on the new items.
+///
+/// It must not print anything as the list is displayed, errors are returned
+/// to be displayed in the title of the box and keep the current items.
+/// Entries of the empty search list are replaced by the new item with the
+/// same key, keeping their note, and removed if there is none.
+pub struct Reload<'k, K>(pub &'k dyn Fn() -> Result<Vec<ListEntry<K>>, String>);
+
+impl<'k, K> Clone for Reload<'k, K> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'k, K> Copy for Reload<'k, K> {}
+
+impl<'k, K> fmt::Debug for Reload<'k, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Reload").finish_non_exhaustive()
+    }
 }
 
 /// Main type to setup the list search.
 ///
-/// ```
+/// ```no_run
 /// # fn main() -> std::io::Result<()> {
 /// let list: Vec<_> = ["Alpha", "Beta", "Omega"]
 ///     .into_iter()
 ///     .enumerate()
-///     .map(|(index, item)| ichoose::ListEntry {
-///         key: index.to_string(),
-///         name: item.to_string(),
-///     })
+///     .map(|(index, item)| ichoose::ListEntry::new(index.to_string(), item))
 ///     .collect();
 ///
 /// let choices = ichoose::ListSearch {
-///    items: &list,
+///    items: list,
 ///    extra: ichoose::ListSearchExtra {
 ///        title: "Exemple title".to_string(),
 ///        ..Default::default()
@@ -69,61 +322,220 @@ pub struct ListSearch<'k, K> {
     /// Customization options.
     pub extra: ListSearchExtra<'k, K>,
     /// The main list we want to search into.
-    pub items: &'k [ListEntry<K>],
+    pub items: Vec<ListEntry<K>>,
 }
 
-struct ListSearchRunner<'c, 'k, K> {
+struct ListSearchRunner<'c, 'k, K: Clone> {
     config: &'c ListSearch<'k, K>,
 
-    /// List currently being displayed (filtered by search).
+    /// Items to search into, which are replaced when reloaded.
+    items: Cow<'c, [ListEntry<K>]>,
+    /// List displayed if the search input is empty, which is updated from
+    /// the items when reloaded.
+    empty_search_list: Option<Cow<'c, [ListEntry<K>]>>,
+    /// Indices of the entries currently being displayed (filtered by search),
+    /// in `items` or in the empty search list if `showing_empty_search_list`.
     /// Used to properly find which entry is selected when pressing Enter.
-    displayed_list: Vec<&'k ListEntry<K>>,
+    displayed_list: Vec<usize>,
+    /// Are the displayed entries the ones of the empty search list?
+    showing_empty_search_list: bool,
     /// Content of the search input field.
     search_input: String,
     /// Set of selected items.
     selected_items: BTreeSet<K>,
+    /// Items queued with Tab, in order.
+    queued_items: Vec<K>,
+    /// Entry filter, which is evaluated lazily.
+    entry_filter: CachedFilter<'k, K>,
+    /// Key of the triggered action.
+    action: Option<char>,
     /// State of the TUI List.
     ui_list_state: ListState,
+    /// Outcome of the last reload, displayed until the next key press.
+    reload_message: Option<Result<String, String>>,
     /// Should the TUI exit?
     exit: bool,
 }
 
+/// Entry filter with its results for each key.
+struct CachedFilter<'k, K> {
+    filter: Option<EntryFilter<'k, K>>,
+    results: BTreeMap<K, bool>,
+}
+
+impl<'k, K: Ord + Clone> CachedFilter<'k, K> {
+    fn new(filter: Option<EntryFilter<'k, K>>) -> Self {
+        Self {
+            filter,
+            results: BTreeMap::new(),
+        }
+    }
+
+    fn check(&mut self, item: &ListEntry<K>) -> bool {
+        let Some(EntryFilter(filter)) = self.filter else {
+            return true;
+        };
+
+        *self
+            .results
+            .entry(item.key.clone())
+            .or_insert_with(|| filter(item))
+    }
+}
+
 impl<'k, K: Ord + Clone> ListSearch<'k, K> {
+    /// Let the user select items, returning the selected keys.
     pub fn run(&self) -> io::Result<BTreeSet<K>> {
-        ListSearchRunner {
-            config: self,
+        Ok(self.run_detailed()?.selected)
+    }
+
+    /// Let the user select items, returning the selected keys along with
+    /// additional information about the session.
+    ///
+    /// If stderr is not a terminal, a simple numbered list is printed instead
+    /// of the TUI and the choice is read from stdin.
+    pub fn run_detailed(&self) -> io::Result<ListSearchOutcome<K>> {
+        let runner = ListSearchRunner::new(self);
+
+        if io::stderr().is_tty() {
+            runner.run()
+        } else {
+            runner.choose_in_list()
+        }
+    }
+}
+
+impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
+    fn new(config: &'c ListSearch<'k, K>) -> Self {
+        Self {
+            config,
+            items: Cow::Borrowed(&config.items),
+            empty_search_list: config.extra.empty_search_list.map(Cow::Borrowed),
             displayed_list: Vec::new(),
-            search_input: String::new(),
+            showing_empty_search_list: false,
+            search_input: config.extra.initial_search.clone(),
             selected_items: BTreeSet::new(),
+            queued_items: Vec::new(),
+            entry_filter: CachedFilter::new(config.extra.entry_filter),
+            action: None,
             ui_list_state: ListState::default().tap_mut(|v| v.select(Some(0))),
+            reload_message: None,
             exit: false,
         }
-        .run()
     }
-}
 
-impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
     fn update_displayed_list(&mut self) {
-        if let Some(alt_list) = self.config.extra.empty_search_list {
+        if let Some(alt_list) = &self.empty_search_list {
             if self.search_input.is_empty() {
-                self.displayed_list = alt_list.iter().collect();
+                self.showing_empty_search_list = true;
+                self.displayed_list = (0..alt_list.len())
+                    .filter(|&i| self.entry_filter.check(&alt_list[i]))
+                    .collect();
                 return;
             }
         }
 
-        let search = self.search_input.to_lowercase();
-        let search: Vec<_> = search.split(',').map(|s| s.trim()).collect();
+        self.update_displayed_items();
+    }
 
-        self.displayed_list = self
-            .config
-            .items
-            .iter()
-            .filter(|item| search_filter(&item.name, &search))
+    /// Display the items matching the search input, even if it is empty.
+    fn update_displayed_items(&mut self) {
+        let search = split_terms(&normalize(&self.search_input));
+
+        // Cheap search filter is applied first, so that the entry filter is
+        // only evaluated on entries that could be displayed.
+        self.showing_empty_search_list = false;
+        self.displayed_list = (0..self.items.len())
+            .filter(|&i| search_filter(&self.items[i], &search))
+            .filter(|&i| self.entry_filter.check(&self.items[i]))
             .collect::<Vec<_>>()
-            .tap_mut(|v| v.sort_by_key(|item| &item.name));
+            .tap_mut(|v| {
+                let items = &self.items;
+                v.sort_by(|&a, &b| compare_entries(&items[a], &items[b]))
+            });
+    }
+
+    /// List the displayed entries are taken from.
+    fn displayed_source(&self) -> &[ListEntry<K>] {
+        match &self.empty_search_list {
+            Some(alt_list) if self.showing_empty_search_list => alt_list,
+            _ => &self.items,
+        }
+    }
+
+    /// Entries currently being displayed.
+    fn displayed_entries(&self) -> impl Iterator<Item = &ListEntry<K>> {
+        let source = self.displayed_source();
+        self.displayed_list.iter().map(move |&i| &source[i])
+    }
+
+    /// Displayed entry at `index` in the displayed list.
+    fn displayed_entry(&self, index: usize) -> Option<&ListEntry<K>> {
+        let i = *self.displayed_list.get(index)?;
+        Some(&self.displayed_source()[i])
+    }
+
+    /// Key of the entry with the provided hotkey, among the ones which can be
+    /// displayed.
+    fn find_hotkey_entry(&mut self, key: char) -> Option<K> {
+        self.items
+            .iter()
+            .filter(|item| item.hotkey == Some(key))
+            .find(|item| self.entry_filter.check(item))
+            .map(|item| item.key.clone())
+    }
+
+    /// Update the displayed list after the search input changed. The
+    /// highlighted entry and scroll position are kept if the displayed
+    /// entries didn't change, otherwise the first entry is highlighted.
+    fn update_search(&mut self) {
+        let previous: Vec<_> = self
+            .displayed_entries()
+            .map(|item| item.key.clone())
+            .collect();
+        self.update_displayed_list();
+
+        if !self.displayed_entries().map(|item| &item.key).eq(&previous) {
+            self.ui_list_state.select(Some(0));
+        }
+    }
+
+    /// Replace the items by the ones provided by the reload callback.
+    fn reload(&mut self) {
+        let Some(Reload(reload)) = self.config.extra.reload else {
+            return;
+        };
+
+        match reload() {
+            Ok(items) => {
+                // Entries of the empty search list are updated from the new
+                // items (keeping their note), or removed if they are gone.
+                if let Some(list) = &self.empty_search_list {
+                    let reloaded: BTreeMap<_, _> =
+                        items.iter().map(|item| (&item.key, item)).collect();
+                    let list = list
+                        .iter()
+                        .filter_map(|entry| {
+                            let &item = reloaded.get(&entry.key)?;
+                            Some(ListEntry {
+                                note: entry.note.clone(),
+                                ..item.clone()
+                            })
+                        })
+                        .collect();
+                    self.empty_search_list = Some(Cow::Owned(list));
+                }
+
+                self.items = Cow::Owned(items);
+                self.entry_filter.results.clear();
+                self.update_displayed_list();
+                self.reload_message = Some(Ok(format!("Reloaded {} entries", self.items.len())));
+            }
+            Err(e) => self.reload_message = Some(Err(e)),
+        }
     }
 
-    pub fn run(self) -> io::Result<BTreeSet<K>> {
+    pub fn run(self) -> io::Result<ListSearchOutcome<K>> {
         let mut stderr = io::stderr();
 
         execute!(stderr, EnterAlternateScreen)?;
@@ -141,7 +553,7 @@ impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
     fn run_inner<T: ratatui::backend::Backend>(
         mut self,
         terminal: &mut Terminal<T>,
-    ) -> io::Result<BTreeSet<K>> {
+    ) -> io::Result<ListSearchOutcome<K>> {
         self.update_displayed_list();
 
         while !self.exit {
@@ -149,7 +561,117 @@ impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
             self.handle_events()?;
         }
 
-        Ok(self.selected_items)
+        Ok(self.into_outcome())
+    }
+
+    /// Fallback used without a terminal: items matching the initial search
+    /// are printed with a number on stderr, and the numbers of the chosen
+    /// ones are read from stdin. Actions are not available.
+    fn choose_in_list(mut self) -> io::Result<ListSearchOutcome<K>> {
+        // The empty search list can't be left by typing a search, so all
+        // items are listed instead.
+        self.update_displayed_items();
+
+        let mut stderr = io::stderr();
+        writeln!(stderr, "{}", self.config.extra.title.trim())?;
+        if !self.config.extra.text.is_empty() {
+            writeln!(stderr, "{}", self.config.extra.text)?;
+        }
+
+        if self.displayed_list.is_empty() {
+            writeln!(stderr, "No entries to choose from.")?;
+            return Ok(self.into_outcome());
+        }
+
+        // Numbers are right-aligned, and colored unless disabled.
+        let width = self.displayed_list.len().to_string().len();
+        let color = self.config.extra.color.enabled();
+
+        for (i, item) in self.displayed_entries().enumerate() {
+            let number = format!("{:>width$}.", i + 1);
+            let note = item
+                .note
+                .as_ref()
+                .map(|note| format!("  {note}"))
+                .unwrap_or_default();
+            if color {
+                // Bold blue.
+                writeln!(stderr, "\x1b[1;34m{number}\x1b[0m {}{note}", item.name)?;
+            } else {
+                writeln!(stderr, "{number} {}{note}", item.name)?;
+            }
+        }
+
+        let prompt = if self.config.extra.multi_select {
+            "Choices (numbers separated by spaces or commas, empty to quit): "
+        } else {
+            "Choice (empty to quit): "
+        };
+
+        loop {
+            write!(stderr, "{prompt}")?;
+            stderr.flush()?;
+
+            let mut buf = String::new();
+            // Nothing more to read, handled like an empty answer.
+            if io::stdin().read_line(&mut buf)? == 0 {
+                break;
+            }
+
+            let indices = match parse_choices(&buf, self.displayed_list.len()) {
+                Ok(indices) if indices.len() > 1 && !self.config.extra.multi_select => {
+                    writeln!(stderr, "Only one choice can be made.")?;
+                    continue;
+                }
+                Ok(indices) => indices,
+                Err(e) => {
+                    writeln!(stderr, "{e}")?;
+                    continue;
+                }
+            };
+
+            let keys: Vec<_> = indices
+                .into_iter()
+                .filter_map(|index| self.displayed_entry(index))
+                .map(|item| item.key.clone())
+                .collect();
+            self.selected_items.extend(keys);
+            break;
+        }
+
+        Ok(self.into_outcome())
+    }
+
+    fn into_outcome(self) -> ListSearchOutcome<K> {
+        // Entries can also be in the empty search list, the first one found
+        // for each key is kept.
+        let mut all_entries = BTreeMap::new();
+        for item in self
+            .items
+            .iter()
+            .chain(self.empty_search_list.iter().flat_map(|list| list.iter()))
+        {
+            all_entries.entry(&item.key).or_insert(item);
+        }
+
+        let entries = self
+            .selected_items
+            .iter()
+            .filter_map(|key| all_entries.get(key).map(|&item| item.clone()))
+            .collect();
+        let queued = self
+            .queued_items
+            .iter()
+            .filter_map(|key| all_entries.get(key).map(|&item| item.clone()))
+            .collect();
+
+        ListSearchOutcome {
+            selected: self.selected_items,
+            entries,
+            queued,
+            search: self.search_input,
+            action: self.action,
+        }
     }
 
     fn render_frame(&mut self, frame: &mut Frame) {
@@ -169,11 +691,33 @@ impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) {
+        let ctrl = key_event.modifiers.contains(KeyModifiers::CONTROL);
+        let multi_select = self.config.extra.multi_select;
+        self.reload_message = None;
+
         match key_event.code {
             KeyCode::Esc => {
                 self.selected_items = BTreeSet::new();
+                self.queued_items.clear();
                 self.exit = true;
             }
+            KeyCode::Tab if self.config.extra.queue && !multi_select => {
+                let Some(key) = self
+                    .ui_list_state
+                    .selected()
+                    .and_then(|index| self.displayed_entry(index))
+                    .map(|item| item.key.clone())
+                else {
+                    return;
+                };
+
+                match self.queued_items.iter().position(|queued| queued == &key) {
+                    Some(position) => {
+                        self.queued_items.remove(position);
+                    }
+                    None => self.queued_items.push(key),
+                }
+            }
             KeyCode::Enter => {
                 self.exit = true;
 
@@ -185,21 +729,76 @@ impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
                     return;
                 };
 
-                let Some(item) = self.displayed_list.get(selected_index) else {
+                let Some(key) = self
+                    .displayed_entry(selected_index)
+                    .map(|item| item.key.clone())
+                else {
                     return;
                 };
 
-                self.selected_items.insert(item.key.clone());
+                self.selected_items.insert(key);
+            }
+            KeyCode::Char('x') if ctrl && self.config.extra.multi_select => {
+                self.selected_items.clear();
+            }
+            KeyCode::Char('n') if ctrl && self.config.extra.multi_select => {
+                for item in self.items.iter() {
+                    if !self.selected_items.remove(&item.key) {
+                        self.selected_items.insert(item.key.clone());
+                    }
+                }
+            }
+            KeyCode::Char('r') if ctrl && self.config.extra.reload.is_some() => {
+                self.reload();
+            }
+            KeyCode::Char(c)
+                if ctrl
+                    && self
+                        .config
+                        .extra
+                        .actions
+                        .iter()
+                        .any(|action| action.key == c) =>
+            {
+                if !self.config.extra.multi_select || self.selected_items.is_empty() {
+                    let highlighted = self
+                        .ui_list_state
+                        .selected()
+                        .and_then(|index| self.displayed_entry(index))
+                        .map(|item| item.key.clone());
+
+                    self.selected_items.clear();
+                    self.selected_items.extend(highlighted);
+                }
+
+                // Nothing to apply the action to.
+                if self.selected_items.is_empty() {
+                    return;
+                }
+
+                // Actions only apply to the highlighted entry.
+                self.queued_items.clear();
+
+                self.action = Some(c);
+                self.exit = true;
+            }
+            KeyCode::Char(c) if !ctrl && self.search_input.is_empty() && !multi_select => {
+                if let Some(key) = self.find_hotkey_entry(c) {
+                    self.selected_items.insert(key);
+                    self.exit = true;
+                    return;
+                }
+
+                self.search_input.push(c);
+                self.update_search();
             }
-            KeyCode::Char(c) => {
+            KeyCode::Char(c) if !ctrl => {
                 self.search_input.push(c);
-                self.ui_list_state.select(Some(0));
-                self.update_displayed_list();
+                self.update_search();
             }
             KeyCode::Backspace => {
                 self.search_input.pop();
-                self.ui_list_state.select(Some(0));
-                self.update_displayed_list();
+                self.update_search();
             }
             KeyCode::Up => {
                 self.ui_list_state.select_previous();
@@ -208,18 +807,16 @@ impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
                 self.ui_list_state.select_next();
             }
             KeyCode::Left if self.config.extra.multi_select => {
-                if self
-                    .displayed_list
-                    .iter()
-                    .any(|item| self.selected_items.contains(&item.key))
-                {
-                    for item in self.displayed_list.iter() {
-                        self.selected_items.remove(&item.key);
+                let keys: Vec<_> = self
+                    .displayed_entries()
+                    .map(|item| item.key.clone())
+                    .collect();
+                if keys.iter().any(|key| self.selected_items.contains(key)) {
+                    for key in &keys {
+                        self.selected_items.remove(key);
                     }
                 } else {
-                    for item in self.displayed_list.iter() {
-                        self.selected_items.insert(item.key.clone());
-                    }
+                    self.selected_items.extend(keys);
                 }
             }
             KeyCode::Right if self.config.extra.multi_select => {
@@ -227,14 +824,15 @@ impl<'c, 'k, K: Ord + Clone> ListSearchRunner<'c, 'k, K> {
                     return;
                 };
 
-                let Some(item) = self.displayed_list.get(selected_index) else {
+                let Some(key) = self
+                    .displayed_entry(selected_index)
+                    .map(|item| item.key.clone())
+                else {
                     return;
                 };
 
-                if self.selected_items.contains(&item.key) {
-                    self.selected_items.remove(&item.key);
-                } else {
-                    self.selected_items.insert(item.key.clone());
+                if !self.selected_items.remove(&key) {
+                    self.selected_items.insert(key);
                 }
             }
             _ => {}
@@ -253,6 +851,19 @@ impl<'c, 'k, K: Ord + Clone> Widget for &mut ListSearchRunner<'c, 'k, K> {
         if self.config.extra.multi_select {
             instructions.add_instruction("Toogle select", "Right");
             instructions.add_instruction("Toogle all", "Left");
+            instructions.add_instruction("Clear", "Ctrl+X");
+            instructions.add_instruction("Invert", "Ctrl+N");
+        } else if self.config.extra.queue {
+            instructions.add_instruction("Queue", "Tab");
+        }
+
+        for action in &self.config.extra.actions {
+            let keys = format!("Ctrl+{}", action.key.to_ascii_uppercase());
+            instructions.add_instruction(&action.name, &keys);
+        }
+
+        if self.config.extra.reload.is_some() {
+            instructions.add_instruction("Reload", "Ctrl+R");
         }
 
         instructions.add_instruction("Confirm", "Enter");
@@ -261,7 +872,7 @@ impl<'c, 'k, K: Ord + Clone> Widget for &mut ListSearchRunner<'c, 'k, K> {
         instructions.push(" ".into());
 
         let instructions = Title::from(instructions);
-        let block = Block::bordered()
+        let mut block = Block::bordered()
             .title(title.alignment(Alignment::Center))
             .title(
                 instructions
@@ -271,15 +882,49 @@ impl<'c, 'k, K: Ord + Clone> Widget for &mut ListSearchRunner<'c, 'k, K> {
             .border_set(border::THICK)
             .padding(Padding::horizontal(1));
 
+        if let Some(label) = &self.config.extra.count_label {
+            let mut counts = format!(" {} {label}", self.items.len());
+            if let Some(list) = &self.empty_search_list {
+                counts.push_str(&format!(", {} recent", list.len()));
+            }
+            counts.push(' ');
+            block = block.title(Title::from(counts.dark_gray()).alignment(Alignment::Left));
+        }
+
+        match &self.reload_message {
+            Some(Ok(message)) => {
+                let message = format!(" {message} ").green().bold();
+                block = block.title(Title::from(message).alignment(Alignment::Right));
+            }
+            Some(Err(e)) => {
+                let message = format!(" {e} ").red().bold();
+                block = block.title(Title::from(message).alignment(Alignment::Right));
+            }
+            None => (),
+        }
+
+        if self.config.extra.multi_select {
+            let count = format!(" {} selected ", self.selected_items.len());
+            block = block.title(Title::from(count.bold()).alignment(Alignment::Right));
+        } else if !self.queued_items.is_empty() {
+            let count = format!(" {} queued ", self.queued_items.len());
+            block = block.title(Title::from(count.bold()).alignment(Alignment::Right));
+        }
+
         // Layout
+        let (padding, text_height) = if self.config.extra.compact {
+            (0, 0)
+        } else {
+            (1, 5)
+        };
         let [search_bar, _padding1, list_area, _padding2, extra_text] = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
                 Constraint::Length(1),
-                Constraint::Length(1),
+                Constraint::Length(padding),
                 Constraint::Min(3),
-                Constraint::Length(1),
-                Constraint::Max(5),
+                Constraint::Length(padding),
+                Constraint::Max(text_height),
             ])
             .areas(block.inner(area));
 
@@ -302,26 +947,69 @@ impl<'c, 'k, K: Ord + Clone> Widget for &mut ListSearchRunner<'c, 'k, K> {
             .render(search_area, buf);
 
         // Render list
+        // Fields are borrowed separately from the list state.
+        let source: &[ListEntry<K>] = match &self.empty_search_list {
+            Some(alt_list) if self.showing_empty_search_list => alt_list,
+            _ => &self.items,
+        };
         let list: Vec<_> = self
             .displayed_list
             .iter()
+            .map(|&i| &source[i])
             .map(|item| {
+                let mut line = Line::default();
+                let mut indent = "";
+
                 if self.config.extra.multi_select {
                     let c = if self.selected_items.contains(&item.key) {
                         "X"
                     } else {
                         " "
                     };
-                    format!("[{c}] {}", item.name)
-                } else {
-                    item.name.clone()
+                    line.push_span(format!("[{c}] "));
+                    indent = "    ";
+                }
+
+                line.push_span(item.name.as_str());
+
+                if let Some(hotkey) = item.hotkey {
+                    line.push_span(format!(" <{hotkey}>").blue().bold());
+                }
+
+                if let Some(position) = self.queued_items.iter().position(|key| key == &item.key) {
+                    line.push_span(format!(" (queued {})", position + 1).yellow().bold());
+                }
+
+                for badge in &item.badges {
+                    line.push_span(format!(" [{badge}]").dark_gray());
+                }
+
+                for tag in &item.tags {
+                    line.push_span(format!(" #{tag}").dark_gray().italic());
+                }
+
+                if let Some(note) = &item.note {
+                    line.push_span(format!("  {note}").dark_gray());
+                }
+
+                let description = item
+                    .description
+                    .as_deref()
+                    .filter(|_| self.config.extra.show_descriptions);
+
+                match description {
+                    Some(description) => ListItem::new(vec![
+                        line,
+                        Line::from(format!("{indent}{description}").dark_gray()),
+                    ]),
+                    None => ListItem::new(line),
                 }
             })
             .collect();
 
         let list = List::new(list)
-            .highlight_style(Style::new().bold().blue())
-            .highlight_symbol("> ")
+            .highlight_style(self.config.extra.highlight_style)
+            .highlight_symbol(&self.config.extra.highlight_symbol)
             .highlight_spacing(HighlightSpacing::Always)
             .scroll_padding(1);
         StatefulWidget::render(&list, list_area, buf, &mut self.ui_list_state);
@@ -334,18 +1022,112 @@ impl<'c, 'k, K: Ord + Clone> Widget for &mut ListSearchRunner<'c, 'k, K> {
 
         // Render block
         block.render(area, buf);
+
+        if !self.config.extra.color.enabled() {
+            for cell in &mut buf.content {
+                cell.fg = Color::Reset;
+                cell.bg = Color::Reset;
+            }
+        }
     }
 }
 
-fn search_filter(name: &str, search_items: &[&str]) -> bool {
-    let name_lower = name.to_lowercase();
-    for item in search_items {
-        if !name_lower.contains(item) {
-            return false;
+/// Lowercase text without accents (`Café` becomes `cafe`), used to compare
+/// search terms.
+pub fn normalize(text: &str) -> String {
+    deunicode::deunicode(text).to_lowercase()
+}
+
+/// Parse numbers (starting from 1) separated by spaces or commas into indices
+/// of a list of `len` entries.
+fn parse_choices(input: &str, len: usize) -> Result<Vec<usize>, String> {
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|choice| !choice.is_empty())
+        .map(|choice| match choice.parse::<usize>() {
+            Ok(number) if (1..=len).contains(&number) => Ok(number - 1),
+            Ok(_) => Err(format!(
+                "{choice} is out of range, choose between 1 and {len}."
+            )),
+            Err(_) => Err(format!("{choice} is not a number.")),
+        })
+        .collect()
+}
+
+/// Split a search input into terms separated by commas, which are trimmed.
+/// The text of a term can be quoted with `"` (after its `!`, `#`, `~` or `@`
+/// prefix) to contain commas, leading and trailing spaces or characters
+/// read as prefixes, `""` being a literal quote inside quotes.
+fn split_terms(search: &str) -> Vec<SearchTerm> {
+    let mut terms = Vec::new();
+    let mut rest = search;
+
+    loop {
+        let (term, next) = parse_term(rest);
+        terms.push(term);
+        match next {
+            Some(next) => rest = next,
+            None => return terms,
         }
     }
+}
 
-    true
+/// Parse the first term of `search`, returning the rest of the search after
+/// its comma if there is one.
+fn parse_term(search: &str) -> (SearchTerm, Option<&str>) {
+    let mut rest = search.trim_start();
+
+    let negated = match rest.strip_prefix('!') {
+        Some(after) => {
+            rest = after;
+            true
+        }
+        None => false,
+    };
+    let (kind, after) = TermKind::split_prefix(rest);
+    rest = after;
+
+    let mut text = String::new();
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let mut chars = quoted.char_indices().peekable();
+        // The closing quote may not be typed yet.
+        rest = "";
+        while let Some((i, c)) = chars.next() {
+            if c != '"' {
+                text.push(c);
+            } else if chars.next_if(|&(_, c)| c == '"').is_some() {
+                text.push('"');
+            } else {
+                rest = &quoted[i + 1..];
+                break;
+            }
+        }
+    }
+
+    // Text after the closing quote is kept as is.
+    let (unquoted, next) = match rest.split_once(',') {
+        Some((unquoted, next)) => (unquoted, Some(next)),
+        None => (rest, None),
+    };
+    text.push_str(unquoted.trim_end());
+
+    let term = SearchTerm {
+        negated,
+        kind,
+        text,
+    };
+    (term, next)
+}
+
+/// Check if the entry matches all search terms, except terms starting with
+/// `!` which must not match.
+fn search_filter<K>(entry: &ListEntry<K>, terms: &[SearchTerm]) -> bool {
+    terms.iter().all(|term| match term.negated {
+        // Ignored until the term is typed.
+        true if term.text.is_empty() => true,
+        true => !entry.matches_text(term.kind, &term.text),
+        false => entry.matches_text(term.kind, &term.text),
+    })
 }
 
 trait AddInstruction {
@@ -358,3 +1140,376 @@ impl<'k> AddInstruction for Vec<Span<'k>> {
         self.push(format!("<{keys}>").blue().bold());
     }
 }
+
+/// Sort entries by decreasing priority, then by name.
+pub fn sort_entries<K>(entries: &mut [ListEntry<K>]) {
+    entries.sort_by(compare_entries);
+}
+
+/// Order of entries in the list, see [`sort_entries`].
+fn compare_entries<K>(a: &ListEntry<K>, b: &ListEntry<K>) -> std::cmp::Ordering {
+    b.priority
+        .cmp(&a.priority)
+        .then_with(|| a.name.cmp(&b.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_choices() {
+        assert_eq!(parse_choices("2\n", 3), Ok(vec![1]));
+        assert_eq!(parse_choices(" 1, 3 2", 3), Ok(vec![0, 2, 1]));
+        assert_eq!(parse_choices("300", 300), Ok(vec![299]));
+        assert_eq!(parse_choices("\n", 3), Ok(vec![]));
+        assert!(parse_choices("0", 3).is_err());
+        assert!(parse_choices("4", 3).is_err());
+        assert!(parse_choices("one", 3).is_err());
+    }
+
+    #[test]
+    fn search_ignores_case_and_accents() {
+        let entry = ListEntry {
+            tags: vec!["Déploiement".to_string()],
+            ..ListEntry::new(0, "Café Deploy")
+        };
+
+        assert!(entry.matches(&normalize("cafe")));
+        assert!(entry.matches(&normalize("CAFÉ")));
+        assert!(entry.matches(&normalize("#deploiement")));
+        assert!(!entry.matches(&normalize("tea")));
+    }
+
+    #[test]
+    fn sorts_by_priority_then_name() {
+        let entries = [
+            ListEntry::new(0, "b"),
+            ListEntry {
+                priority: -1,
+                ..ListEntry::new(1, "a")
+            },
+            ListEntry::new(2, "a"),
+            ListEntry {
+                priority: 5,
+                ..ListEntry::new(3, "z")
+            },
+        ];
+        let mut sorted = entries.to_vec();
+        sort_entries(&mut sorted);
+
+        let keys: Vec<_> = sorted.iter().map(|entry| entry.key).collect();
+        assert_eq!(keys, [3, 2, 0, 1]);
+    }
+
+    #[test]
+    fn tab_queues_entries() {
+        let search = ListSearch {
+            items: vec![ListEntry::new(0, "a"), ListEntry::new(1, "b")],
+            extra: ListSearchExtra {
+                queue: true,
+                ..Default::default()
+            },
+        };
+        let mut runner = ListSearchRunner::new(&search);
+        runner.update_displayed_list();
+
+        let press = |runner: &mut ListSearchRunner<_>, code| {
+            runner.handle_key_event(KeyEvent::new(code, KeyModifiers::NONE))
+        };
+        press(&mut runner, KeyCode::Down);
+        press(&mut runner, KeyCode::Tab);
+        press(&mut runner, KeyCode::Up);
+        press(&mut runner, KeyCode::Enter);
+
+        let outcome = runner.into_outcome();
+        let queued: Vec<_> = outcome.queued.iter().map(|entry| entry.key).collect();
+        let entries: Vec<_> = outcome.entries.iter().map(|entry| entry.key).collect();
+        assert_eq!(queued, [1]);
+        assert_eq!(entries, [0]);
+    }
+
+    #[test]
+    fn search_negated_terms() {
+        let entry = ListEntry {
+            tags: vec!["k8s".to_string()],
+            ..ListEntry::new(0, "Deploy production")
+        };
+
+        let filter = |search: &str| search_filter(&entry, &split_terms(search));
+        assert!(filter("deploy, !staging"));
+        assert!(!filter("deploy, !prod"));
+        assert!(!filter("!#k8s"));
+        assert!(filter("deploy, !"));
+        assert!(filter("deploy, !#"));
+    }
+
+    #[test]
+    fn splits_quoted_terms() {
+        fn term(negated: bool, kind: TermKind, text: &str) -> SearchTerm {
+            SearchTerm {
+                negated,
+                kind,
+                text: text.to_string(),
+            }
+        }
+        let any = |text| term(false, TermKind::Any, text);
+
+        assert_eq!(split_terms(" deploy ,k8s"), [any("deploy"), any("k8s")]);
+        assert_eq!(
+            split_terms(r#""foo, bar", baz"#),
+            [any("foo, bar"), any("baz")]
+        );
+        assert_eq!(split_terms(r#"" spaced ""#), [any(" spaced ")]);
+        assert_eq!(
+            split_terms(r#"!"a,b", #"x y"#),
+            [
+                term(true, TermKind::Any, "a,b"),
+                term(false, TermKind::Tag, "x y")
+            ]
+        );
+        assert_eq!(split_terms(""), [any("")]);
+        assert_eq!(
+            split_terms(r#""say ""hi""", a"b"#),
+            [any(r#"say "hi""#), any(r#"a"b"#)]
+        );
+        // The closing quote may not be typed yet.
+        assert_eq!(
+            split_terms(r#"@"docker, "#),
+            [term(false, TermKind::Origin, "docker, ")]
+        );
+    }
+
+    #[test]
+    fn quoted_terms_are_literal() {
+        let entry = ListEntry::new(0, "!foo #1 fix");
+        let filter = |search: &str| search_filter(&entry, &split_terms(search));
+
+        let quoted = split_terms(r#""!foo""#);
+        assert!(!quoted[0].negated);
+        assert_eq!(quoted[0].text, "!foo");
+        assert!(filter(r#""!foo""#));
+        assert!(!filter("!foo"));
+        assert!(filter(r##""#1 fix""##));
+        assert!(!filter("#1 fix"));
+    }
+
+    #[test]
+    fn ctrl_r_reloads_items() {
+        let reload = || Ok(vec![ListEntry::new(0, "a"), ListEntry::new(1, "b")]);
+        let search = ListSearch {
+            items: vec![ListEntry::new(0, "a")],
+            extra: ListSearchExtra {
+                reload: Some(Reload(&reload)),
+                ..Default::default()
+            },
+        };
+        let mut runner = ListSearchRunner::new(&search);
+        runner.update_displayed_list();
+        assert_eq!(runner.displayed_list.len(), 1);
+
+        runner.handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        let names: Vec<_> = runner
+            .displayed_entries()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(
+            runner.reload_message,
+            Some(Ok("Reloaded 2 entries".to_string()))
+        );
+    }
+
+    #[test]
+    fn fallback_lists_items_instead_of_empty_search_list() {
+        let recent = [ListEntry::new(1, "b")];
+        let search = ListSearch {
+            items: vec![ListEntry::new(0, "a"), ListEntry::new(1, "b")],
+            extra: ListSearchExtra {
+                empty_search_list: Some(&recent),
+                ..Default::default()
+            },
+        };
+        let mut runner = ListSearchRunner::new(&search);
+
+        runner.update_displayed_list();
+        assert_eq!(runner.displayed_list.len(), 1);
+
+        runner.update_displayed_items();
+        let names: Vec<_> = runner
+            .displayed_entries()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[test]
+    fn reload_updates_empty_search_list() {
+        let reload = || Ok(vec![ListEntry::new(0, "renamed")]);
+        let recent = [
+            ListEntry {
+                note: Some("2 min ago".to_string()),
+                ..ListEntry::new(0, "a")
+            },
+            ListEntry::new(1, "removed"),
+        ];
+        let search = ListSearch {
+            items: vec![ListEntry::new(0, "a"), ListEntry::new(1, "removed")],
+            extra: ListSearchExtra {
+                empty_search_list: Some(&recent),
+                reload: Some(Reload(&reload)),
+                ..Default::default()
+            },
+        };
+        let mut runner = ListSearchRunner::new(&search);
+        runner.update_displayed_list();
+        assert_eq!(runner.displayed_list.len(), 2);
+
+        runner.handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        let entries: Vec<_> = runner
+            .displayed_entries()
+            .map(|e| (e.name.as_str(), e.note.as_deref()))
+            .collect();
+        assert_eq!(entries, [("renamed", Some("2 min ago"))]);
+    }
+
+    #[test]
+    fn failed_reload_keeps_items() {
+        let reload = || Err("Unable to reload sources".to_string());
+        let search = ListSearch {
+            items: vec![ListEntry::new(0, "a")],
+            extra: ListSearchExtra {
+                reload: Some(Reload(&reload)),
+                ..Default::default()
+            },
+        };
+        let mut runner = ListSearchRunner::new(&search);
+        runner.update_displayed_list();
+
+        runner.handle_key_event(KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL));
+        assert_eq!(runner.displayed_list.len(), 1);
+
+        let area = Rect::new(0, 0, 60, 10);
+        let mut buf = Buffer::empty(area);
+        runner.render(area, &mut buf);
+        let content: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(content.contains("Unable to reload sources"));
+
+        // Message is cleared on the next key press.
+        runner.handle_key_event(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(runner.reload_message, None);
+    }
+
+    #[test]
+    fn search_origin() {
+        let entry = ListEntry {
+            origin: Some("docker-commands.toml".to_string()),
+            ..ListEntry::new(0, "Prune")
+        };
+
+        assert!(entry.matches("@docker-commands"));
+        assert!(!entry.matches("@k8s"));
+        assert!(!entry.matches("docker"));
+        assert!(!ListEntry::new(0, "Prune").matches("@docker"));
+    }
+
+    #[test]
+    fn compact_displays_more_entries() {
+        fn visible_entries(compact: bool) -> usize {
+            let search = ListSearch {
+                items: (0..20)
+                    .map(|i| ListEntry::new(i, format!("entry{i}")))
+                    .collect(),
+                extra: ListSearchExtra {
+                    text: "Some help text".to_string(),
+                    compact,
+                    ..Default::default()
+                },
+            };
+            let mut runner = ListSearchRunner::new(&search);
+            runner.update_displayed_list();
+
+            let area = Rect::new(0, 0, 40, 12);
+            let mut buf = Buffer::empty(area);
+            runner.render(area, &mut buf);
+
+            let content: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+            (0..20)
+                .filter(|i| content.contains(&format!("entry{i} ")))
+                .count()
+        }
+
+        assert_eq!(visible_entries(false), 3);
+        assert_eq!(visible_entries(true), 9);
+    }
+
+    #[test]
+    fn displays_counts() {
+        let recent = [ListEntry::new(1, "b")];
+        let search = ListSearch {
+            items: vec![ListEntry::new(0, "a"), ListEntry::new(1, "b")],
+            extra: ListSearchExtra {
+                empty_search_list: Some(&recent),
+                count_label: Some("commands".to_string()),
+                ..Default::default()
+            },
+        };
+        let mut runner = ListSearchRunner::new(&search);
+        runner.update_displayed_list();
+
+        let area = Rect::new(0, 0, 60, 10);
+        let mut buf = Buffer::empty(area);
+        runner.render(area, &mut buf);
+
+        let content: String = buf.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(content.contains(" 2 commands, 1 recent "));
+    }
+
+    #[test]
+    fn color_choice() {
+        fn has_colors(color: ColorChoice) -> bool {
+            let search = ListSearch {
+                items: vec![ListEntry::new(0, "a")],
+                extra: ListSearchExtra {
+                    color,
+                    ..Default::default()
+                },
+            };
+            let mut runner = ListSearchRunner::new(&search);
+            runner.update_displayed_list();
+
+            let area = Rect::new(0, 0, 40, 10);
+            let mut buf = Buffer::empty(area);
+            runner.render(area, &mut buf);
+            buf.content().iter().any(|cell| cell.fg != Color::Reset)
+        }
+
+        assert!(has_colors(ColorChoice::Always));
+        assert!(!has_colors(ColorChoice::Never));
+    }
+
+    #[test]
+    fn keeps_selection_if_search_results_dont_change() {
+        let search = ListSearch {
+            items: (0..5)
+                .map(|i| ListEntry::new(i, format!("entry{i}")))
+                .collect(),
+            extra: ListSearchExtra::default(),
+        };
+        let mut runner = ListSearchRunner::new(&search);
+        runner.update_displayed_list();
+
+        runner.handle_key_event(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        runner.handle_key_event(KeyEvent::new(KeyCode::Down, KeyModifiers::NONE));
+        assert_eq!(runner.ui_list_state.selected(), Some(2));
+
+        // All entries still match.
+        runner.handle_key_event(KeyEvent::new(KeyCode::Char('e'), KeyModifiers::NONE));
+        assert_eq!(runner.ui_list_state.selected(), Some(2));
+        runner.handle_key_event(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        assert_eq!(runner.ui_list_state.selected(), Some(2));
+
+        runner.handle_key_event(KeyEvent::new(KeyCode::Char('4'), KeyModifiers::NONE));
+        assert_eq!(runner.ui_list_state.selected(), Some(0));
+    }
+}
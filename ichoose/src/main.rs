@@ -20,6 +20,18 @@ pub struct Cli {
     /// Can the user pick multiple choices.
     #[arg(long)]
     multi: bool,
+
+    /// Hide the bottom text and empty rows to display more choices.
+    #[arg(long)]
+    compact: bool,
+
+    /// Symbol displayed before the highlighted choice.
+    #[arg(long)]
+    highlight_symbol: Option<String>,
+
+    /// When to use colors.
+    #[arg(long, value_enum, default_value_t)]
+    color: ichoose::ColorChoice,
 }
 
 impl Cli {
@@ -34,18 +46,25 @@ impl Cli {
                 let key = line_iter.next().expect("missing id").trim();
                 let name = line_iter.next().unwrap_or(key).to_string();
 
-                ichoose::ListEntry { key, name }
+                ichoose::ListEntry::new(key, name)
             })
             .collect();
 
+        let mut extra = ichoose::ListSearchExtra {
+            title: format!(" {} ", self.title.unwrap_or_else(|| "ichoose".to_string())),
+            text: self.text.unwrap_or_default(),
+            multi_select: self.multi,
+            compact: self.compact,
+            color: self.color,
+            ..Default::default()
+        };
+        if let Some(symbol) = self.highlight_symbol {
+            extra.highlight_symbol = symbol;
+        }
+
         let choices = ichoose::ListSearch {
-            items: &lines,
-            extra: ichoose::ListSearchExtra {
-                title: format!(" {} ", self.title.unwrap_or_else(|| "ichoose".to_string())),
-                text: self.text.unwrap_or_default(),
-                multi_select: self.multi,
-                ..Default::default()
-            },
+            items: lines,
+            extra,
         }
         .run()?;
 
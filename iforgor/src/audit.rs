@@ -0,0 +1,150 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Words in argument labels marking values which should not be written in the
+/// audit log.
+const SECRET_WORDS: [&str; 4] = ["password", "secret", "token", "key"];
+
+/// Outcome of an attempt to run a command.
+#[derive(Debug, Clone, Copy)]
+pub enum Status {
+    /// The script has been run, `None` if it has been started in a new
+    /// terminal.
+    Ran(Option<process::ExitStatus>),
+    /// The user declined to run the command when asked for confirmation.
+    Aborted,
+    /// The command couldn't be run, for example because its `before` hook
+    /// failed.
+    Error,
+}
+
+/// Append a JSON line describing the execution of a command to the audit log.
+pub fn log(
+    path: &Path,
+    id: &str,
+    name: &str,
+    labels: &[String],
+    args: &[String],
+    status: Status,
+) -> anyhow::Result<()> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let args: Vec<_> = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| match labels.get(i) {
+            Some(label) if is_secret(label) => json_string("***"),
+            _ => json_string(arg),
+        })
+        .collect();
+
+    let (status, exit_code) = match status {
+        Status::Ran(None) => ("detached", None),
+        Status::Ran(Some(status)) => match status.code() {
+            Some(code) => ("exited", Some(code)),
+            None => ("signaled", None),
+        },
+        Status::Aborted => ("aborted", None),
+        Status::Error => ("error", None),
+    };
+    let exit_code = exit_code.map_or("null".to_string(), |code| code.to_string());
+
+    let line = format!(
+        "{{\"timestamp\":{timestamp},\"id\":{},\"name\":{},\"args\":[{}],\"status\":\"{status}\",\
+        \"exit_code\":{exit_code}}}\n",
+        json_string(id),
+        json_string(name),
+        args.join(","),
+    );
+
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder)?;
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+/// Check if the label contains one of the secret words, as a whole word so
+/// that "Keyboard layout" is logged but not "API key" or "db_password".
+fn is_secret(label: &str) -> bool {
+    label.split(|c: char| !c.is_alphanumeric()).any(|word| {
+        SECRET_WORDS
+            .iter()
+            .any(|secret| word.eq_ignore_ascii_case(secret))
+    })
+}
+
+/// Quote and escape a string for JSON.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use {super::*, std::os::unix::process::ExitStatusExt};
+
+    #[test]
+    fn appends_json_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.log");
+        let labels = ["Name".to_string(), "API token".to_string()];
+        let args = ["a \"b\"\n".to_string(), "hunter2".to_string()];
+
+        log(
+            &path,
+            "id",
+            "Deploy",
+            &labels,
+            &args,
+            Status::Ran(Some(process::ExitStatus::from_raw(2 << 8))),
+        )
+        .unwrap();
+        log(&path, "id", "Deploy", &[], &[], Status::Ran(None)).unwrap();
+        log(&path, "id", "Deploy", &[], &[], Status::Aborted).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with(
+            r#","id":"id","name":"Deploy","args":["a \"b\"\n","***"],"status":"exited","exit_code":2}"#
+        ));
+        assert!(lines[1].ends_with(r#""args":[],"status":"detached","exit_code":null}"#));
+        assert!(lines[2].ends_with(r#""args":[],"status":"aborted","exit_code":null}"#));
+    }
+
+    #[test]
+    fn secret_words_match_whole_words() {
+        assert!(is_secret("API key"));
+        assert!(is_secret("db_password"));
+        assert!(is_secret("Secret"));
+        assert!(!is_secret("Keyboard layout"));
+        assert!(!is_secret("Monkey name"));
+    }
+}
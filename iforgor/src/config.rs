@@ -0,0 +1,58 @@
+use {
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeSet, path::PathBuf},
+};
+
+/// User settings, read from `config.toml` in the iforgor folder.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Order in which the history is displayed.
+    pub history_order: HistoryOrder,
+    /// Seconds after which the menu is displayed again once a command
+    /// completed, without waiting for Enter to be pressed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_wait_secs: Option<u64>,
+    /// Append each command execution to `audit.log` in the state folder.
+    pub audit: bool,
+    /// Don't ask confirmation before running risky commands, which is less
+    /// safe. Commands with a `confirm_message` still ask it.
+    pub skip_risky_confirm: bool,
+    /// Snippet run after each command of the menu which succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_success: Option<String>,
+    /// Snippet run after each command of the menu which failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+    /// Also display commands in subfolders of the folders matching their
+    /// `only_in_dir` pattern.
+    pub only_in_dir_subdirs: bool,
+    /// History file to use instead of `history.toml` in the state folder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_path: Option<PathBuf>,
+    /// Reload commands from sources each time the menu is opened.
+    pub reload_on_start: bool,
+    /// Display the menu without the help text and empty rows.
+    pub compact_menu: bool,
+    /// Symbol displayed before the highlighted command of the menu.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_symbol: Option<String>,
+    /// Color of the highlighted command of the menu, such as `blue` or
+    /// `#ff8800`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_color: Option<String>,
+    /// Trust ids (see `UserCommand::trust_id`) of commands run without asking
+    /// any confirmation.
+    #[serde(skip_serializing_if = "BTreeSet::is_empty")]
+    pub trusted_ids: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryOrder {
+    /// Most recently ran commands first.
+    #[default]
+    Newest,
+    /// Oldest ran commands first.
+    Oldest,
+}
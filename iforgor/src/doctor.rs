@@ -0,0 +1,217 @@
+use {
+    crate::{find_executable, AppDirs, CommandsSource, Config, History, OnDisk, Registry},
+    serde::{de::DeserializeOwned, Serialize},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt::Display,
+        path::Path,
+    },
+};
+
+#[derive(Default)]
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn success(&mut self, message: impl Display) {
+        println!("✓ {message}");
+    }
+
+    fn failure(&mut self, message: impl Display) {
+        println!("✗ {message}");
+        self.failures += 1;
+    }
+}
+
+/// Check iforgor files and sources, printing a line per check. Returns an
+/// error if any check failed.
+pub fn run(
+    dirs: &AppDirs,
+    config_path: &Path,
+    registry_path: &Path,
+    history_path: &Path,
+) -> anyhow::Result<()> {
+    let mut report = Report::default();
+
+    if dirs.config == dirs.state {
+        report.success(format!("iforgor folder: {}", dirs.config.display()));
+    } else {
+        report.success(format!("Config folder: {}", dirs.config.display()));
+        report.success(format!("State folder: {}", dirs.state.display()));
+    }
+
+    check_file::<Config>(&mut report, "Config", config_path);
+    let registry = check_file::<Registry>(&mut report, "Registry", registry_path);
+    check_file::<History>(&mut report, "History", history_path);
+
+    if let Some(registry) = registry {
+        check_sources(&mut report, &registry);
+    }
+
+    if report.failures > 0 {
+        anyhow::bail!("{} check(s) failed", report.failures);
+    }
+
+    println!("\n🩺 Everything looks good!");
+    Ok(())
+}
+
+fn check_file<T: Serialize + DeserializeOwned + Default>(
+    report: &mut Report,
+    label: &str,
+    path: &Path,
+) -> Option<T> {
+    if !path.exists() {
+        report.success(format!("{label} not created yet: {}", path.display()));
+        return Some(T::default());
+    }
+
+    match OnDisk::<T>::open(path.to_path_buf()) {
+        Ok(content) => {
+            report.success(format!("{label} is valid: {}", path.display()));
+            Some(content.into_inner())
+        }
+        Err(e) => {
+            report.failure(format!(
+                "{label} can't be parsed: {} ({e}). Fix it or run `iforgor --purge-all`",
+                path.display()
+            ));
+            None
+        }
+    }
+}
+
+/// Check a source file without registering it, printing a line per check.
+/// Returns an error if any check failed.
+pub fn validate_source(path: &Path) -> anyhow::Result<()> {
+    let mut report = Report::default();
+
+    match CommandsSource::read(path) {
+        Ok(content) => check_contents(&mut report, vec![(path.display().to_string(), content)]),
+        Err(e) => report.failure(format!("Source can't be parsed: {} ({e})", path.display())),
+    }
+
+    if report.failures > 0 {
+        anyhow::bail!("{} check(s) failed", report.failures);
+    }
+
+    println!("\n🩺 Source looks good!");
+    Ok(())
+}
+
+fn check_sources(report: &mut Report, registry: &Registry) {
+    // Label and content of valid sources.
+    let mut sources = Vec::new();
+
+    for source in &registry.sources {
+        if !source.exists() {
+            report.failure(format!("Source doesn't exist: {}", source.display()));
+            continue;
+        }
+
+        let content = match CommandsSource::read(source) {
+            Ok(content) => content,
+            Err(e) => {
+                report.failure(format!(
+                    "Source can't be parsed: {} ({e})",
+                    source.display()
+                ));
+                continue;
+            }
+        };
+
+        sources.push((source.display().to_string(), content));
+    }
+
+    for var in &registry.env_sources {
+        let label = format!("${var}");
+
+        let Ok(content) = std::env::var(var) else {
+            report.success(format!(
+                "Source from environment variable is not set: {label}"
+            ));
+            continue;
+        };
+
+        match CommandsSource::from_toml(&content) {
+            Ok(content) => sources.push((label, content)),
+            Err(e) => report.failure(format!("Source can't be parsed: {label} ({e})")),
+        }
+    }
+
+    check_contents(report, sources);
+}
+
+/// Check the commands of parsed sources, provided with their label.
+fn check_contents(report: &mut Report, sources: Vec<(String, CommandsSource)>) {
+    // Names and source of commands per id, to find duplicates.
+    let mut ids = BTreeMap::<_, Vec<_>>::new();
+    let mut shells = BTreeSet::new();
+
+    for (label, content) in sources {
+        if content.entries.is_empty() {
+            report.failure(format!(
+                "Source doesn't define any command: {label} (they must be written in \
+                `[[entries]]` tables)"
+            ));
+            continue;
+        }
+
+        let commands: Vec<_> = content
+            .entries
+            .into_iter()
+            .filter(|command| command.is_compatible_with_current_platform())
+            .collect();
+
+        report.success(format!(
+            "Source is valid: {label} ({} commands)",
+            commands.len()
+        ));
+
+        for command in commands {
+            if let Some(only_in_dir) = &command.only_in_dir {
+                if let Err(e) = glob::Pattern::new(only_in_dir) {
+                    report.failure(format!(
+                        "Command \"{}\" has an invalid `only_in_dir` pattern: {e}",
+                        command.name
+                    ));
+                }
+            }
+
+            if command.script.trim().is_empty() {
+                report.failure(format!("Command \"{}\" has an empty script", command.name));
+            }
+
+            shells.insert(command.shell);
+            ids.entry(command.generate_id())
+                .or_default()
+                .push(format!("\"{}\" ({label})", command.name));
+        }
+    }
+
+    let mut duplicates = false;
+    for commands in ids.values().filter(|commands| commands.len() > 1) {
+        duplicates = true;
+        report.failure(format!(
+            "Commands have the same script, only the last one will be registered: {}",
+            commands.join(", ")
+        ));
+    }
+
+    if !duplicates {
+        report.success("No duplicate commands");
+    }
+
+    for shell in shells {
+        let interpreter = shell.interpreter();
+        match find_executable(interpreter) {
+            Some(path) => {
+                report.success(format!("Shell {shell:?} is available: {}", path.display()))
+            }
+            None => report.failure(format!(
+                "Shell {shell:?} is used but `{interpreter}` is not found in the PATH"
+            )),
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use {
+    crate::{Shell, RUNNING_ENV_VAR},
+    anyhow::anyhow,
+    std::{
+        ffi::OsStr,
+        fs::File,
+        io::{self, Read, Write},
+        path::PathBuf,
+        process::{self, Stdio},
+        thread,
+    },
+    tempfile::TempDir,
+};
+
+/// Environment variable containing the folder in which script files are
+/// written, instead of the system temporary folder.
+const TMPDIR_ENV_VAR: &str = "IFORGOR_TMPDIR";
+
+/// Execute a script with the provided shell, with stdio inherited from
+/// `iforgor`.
+pub fn execute_script(
+    script: &str,
+    args: &[String],
+    shell: Shell,
+) -> anyhow::Result<process::ExitStatus> {
+    PreparedScript::new(script, args, shell)?.run()
+}
+
+/// Execute a script with the provided shell, feeding it `stdin` and writing
+/// its outputs into `stdout` and `stderr`.
+pub fn execute_script_with_io(
+    script: &str,
+    args: &[String],
+    shell: Shell,
+    stdin: impl Read + Send,
+    stdout: impl Write,
+    stderr: impl Write + Send,
+) -> anyhow::Result<process::ExitStatus> {
+    PreparedScript::new(script, args, shell)?.run_with_io(stdin, stdout, stderr)
+}
+
+/// Script written in a temporary folder, ready to be executed.
+pub struct PreparedScript {
+    tmp_dir: TempDir,
+    file_path: PathBuf,
+    command: process::Command,
+    keep_temp: bool,
+    stdin: Option<String>,
+    /// Interpreter (and its argument) given the script file if it can't be
+    /// executed directly, such as in a folder mounted `noexec`.
+    interpreter: Option<Vec<String>>,
+}
+
+impl PreparedScript {
+    pub fn new(script: &str, args: &[String], shell: Shell) -> anyhow::Result<Self> {
+        let mut prepared = match shell {
+            Shell::Sh => prepare_script_sh(script, args),
+            Shell::Cmd => prepare_script_cmd(script, args),
+            Shell::Powershell => prepare_script_powershell(script, args),
+        }?;
+
+        prepared.command.env(RUNNING_ENV_VAR, "1");
+
+        Ok(prepared)
+    }
+
+    /// Keep the temporary folder after execution, and display the script file
+    /// path.
+    pub fn keep_temp(mut self, keep_temp: bool) -> Self {
+        self.keep_temp = keep_temp;
+        self
+    }
+
+    /// Add environment variables to the script.
+    pub fn envs<K, V>(mut self, vars: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.command.envs(vars);
+        self
+    }
+
+    /// Text written to the script stdin. If `None` stdin is inherited, which
+    /// allows the script to read from the terminal.
+    pub fn stdin(mut self, stdin: Option<String>) -> Self {
+        self.stdin = stdin;
+        self
+    }
+
+    /// Run the script with inherited stdio (except stdin if text to write into
+    /// it has been provided).
+    pub fn run(mut self) -> anyhow::Result<process::ExitStatus> {
+        let piped_stdin = self.stdin.is_some();
+        let mut child = self.spawn(|command| {
+            if piped_stdin {
+                command.stdin(Stdio::piped());
+            }
+        })?;
+
+        // Written from another thread as the script may not read it before
+        // exiting or producing output.
+        let stdin_thread = match (self.stdin.take(), child.stdin.take()) {
+            (Some(input), Some(mut child_stdin)) => Some(thread::spawn(move || match child_stdin
+                .write_all(input.as_bytes())
+            {
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+                res => res,
+            })),
+            _ => None,
+        };
+
+        let status = child.wait()?;
+
+        if let Some(stdin_thread) = stdin_thread {
+            stdin_thread.join().expect("stdin thread to not panic")?;
+        }
+
+        self.cleanup()?;
+
+        Ok(status)
+    }
+
+    /// Run the script with piped stdio, which are connected to the provided
+    /// reader and writers.
+    pub fn run_with_io(
+        mut self,
+        mut stdin: impl Read + Send,
+        mut stdout: impl Write,
+        mut stderr: impl Write + Send,
+    ) -> anyhow::Result<process::ExitStatus> {
+        let mut child = self.spawn(|command| {
+            command
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped());
+        })?;
+
+        let mut child_stdin = child.stdin.take().expect("stdin to be piped");
+        let mut child_stdout = child.stdout.take().expect("stdout to be piped");
+        let mut child_stderr = child.stderr.take().expect("stderr to be piped");
+
+        // Each pipe is handled in its own thread to avoid a deadlock if the
+        // script fills one of them while we're waiting on another.
+        let copy_result = thread::scope(|s| -> io::Result<()> {
+            let stdin_thread = s.spawn(move || {
+                let res = io::copy(&mut stdin, &mut child_stdin);
+                // Closing stdin for the script to know there is no more input.
+                drop(child_stdin);
+                match res {
+                    // Script may exit without reading all its input.
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(0),
+                    res => res,
+                }
+            });
+            let stderr_thread = s.spawn(|| io::copy(&mut child_stderr, &mut stderr));
+
+            io::copy(&mut child_stdout, &mut stdout)?;
+            stdin_thread.join().expect("stdin thread to not panic")?;
+            stderr_thread.join().expect("stderr thread to not panic")?;
+
+            Ok(())
+        });
+
+        // Wait on the script even if copying failed, to not leave a zombie
+        // process.
+        let status = child.wait()?;
+        copy_result?;
+
+        self.cleanup()?;
+
+        Ok(status)
+    }
+
+    /// Start the script in a new terminal window without waiting for it. The
+    /// temporary folder is not deleted as the script may still be running,
+    /// its path is printed instead.
+    pub fn spawn_in_terminal(self) -> anyhow::Result<()> {
+        let mut command = terminal_command()?;
+        command
+            .arg(self.command.get_program())
+            .args(self.command.get_args())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+
+        for (key, value) in self.command.get_envs() {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+
+        // Some terminal emulators only exit once their window is closed, the
+        // process is waited in the background to not leave a zombie.
+        let mut child = command.spawn()?;
+        thread::spawn(move || child.wait());
+
+        let _ = self.tmp_dir.keep();
+        println!("📂 Script file kept at {}", self.file_path.display());
+
+        Ok(())
+    }
+
+    /// Spawn the script after configuring its command with `setup`. If the
+    /// script file can't be executed, it is given to its interpreter instead.
+    fn spawn(&mut self, setup: impl Fn(&mut process::Command)) -> io::Result<process::Child> {
+        setup(&mut self.command);
+
+        let e = match self.command.spawn() {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => e,
+            res => return res,
+        };
+        let Some((program, args)) = self.interpreter.as_ref().and_then(|i| i.split_first()) else {
+            return Err(e);
+        };
+
+        let mut command = process::Command::new(program);
+        command
+            .args(args)
+            .arg(&self.file_path)
+            .args(self.command.get_args());
+
+        for (key, value) in self.command.get_envs() {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+
+        setup(&mut command);
+        command.spawn()
+    }
+
+    fn cleanup(self) -> anyhow::Result<()> {
+        if self.keep_temp {
+            let _ = self.tmp_dir.keep();
+            println!("\n📂 Script file kept at {}", self.file_path.display());
+        } else {
+            self.tmp_dir.close()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Command opening a new terminal, to which the command to run in it must be
+/// appended. On Linux it is `$TERMINAL -e` (or `x-terminal-emulator -e` if
+/// `TERMINAL` is not set).
+#[cfg(not(target_os = "windows"))]
+fn terminal_command() -> anyhow::Result<process::Command> {
+    let terminal = std::env::var("TERMINAL")
+        .ok()
+        .filter(|terminal| !terminal.trim().is_empty())
+        .or_else(|| {
+            crate::find_executable("x-terminal-emulator").map(|_| "x-terminal-emulator".to_string())
+        })
+        .ok_or(anyhow!(
+            "no terminal emulator found, set the TERMINAL environment variable"
+        ))?;
+
+    // `TERMINAL` can contain options.
+    let mut parts = terminal.split_whitespace();
+    let mut command = process::Command::new(parts.next().expect("terminal to not be empty"));
+    command.args(parts).arg("-e");
+
+    Ok(command)
+}
+
+/// Command opening a new terminal, to which the command to run in it must be
+/// appended. On Windows it is `cmd /C start ""`.
+#[cfg(target_os = "windows")]
+fn terminal_command() -> anyhow::Result<process::Command> {
+    let mut command = process::Command::new("cmd");
+    command.args(["/C", "start", ""]);
+
+    Ok(command)
+}
+
+/// Create a temporary folder in the one provided in `IFORGOR_TMPDIR`, or in
+/// the system temporary folder if it is not set.
+fn create_tmp_dir() -> anyhow::Result<TempDir> {
+    match std::env::var_os(TMPDIR_ENV_VAR).filter(|path| !path.is_empty()) {
+        Some(path) => tempfile::Builder::new()
+            .tempdir_in(path)
+            .map_err(|e| anyhow!("unable to create a folder in `{TMPDIR_ENV_VAR}`: {e}")),
+        None => Ok(tempfile::tempdir()?),
+    }
+}
+
+fn prepare_script_sh(script: &str, args: &[String]) -> anyhow::Result<PreparedScript> {
+    // Sources written on Windows may use CRLF line endings, which `sh` doesn't
+    // understand (`\r` becomes part of the commands).
+    let script = script.replace("\r\n", "\n");
+
+    // Create a temporary folder in which the script file will be
+    // created.
+    let tmp_dir = create_tmp_dir()?;
+    let file_path = tmp_dir.path().join("script");
+
+    // Create the file, write into it and change its permissions (on Linux).
+    // File is closed at the end of scope, which will allow to
+    // execute it after.
+    {
+        let mut tmp_file = File::create(&file_path)?;
+        // Scripts can provide their own shebang to be run by another
+        // interpreter.
+        if !script.starts_with("#!") {
+            tmp_file.write_all(b"#!/bin/sh\n")?;
+        }
+        tmp_file.write_all(script.as_bytes())?;
+        tmp_file.flush()?;
+
+        #[cfg(target_os = "linux")]
+        {
+            // Set permissions to read/execute.
+            use std::os::unix::fs::PermissionsExt;
+            let mut permissions = tmp_file.metadata()?.permissions();
+            permissions.set_mode(0o500);
+            tmp_file.set_permissions(permissions)?;
+        }
+    }
+
+    let mut command = process::Command::new(&file_path);
+    command.args(args);
+
+    Ok(PreparedScript {
+        tmp_dir,
+        file_path,
+        command,
+        keep_temp: false,
+        stdin: None,
+        interpreter: Some(shebang_interpreter(&script)),
+    })
+}
+
+/// Interpreter of a script and its optional argument, read from its shebang
+/// like the kernel does (`/bin/sh` if it doesn't have one).
+fn shebang_interpreter(script: &str) -> Vec<String> {
+    let Some(line) = script.strip_prefix("#!").and_then(|s| s.lines().next()) else {
+        return vec!["/bin/sh".to_string()];
+    };
+
+    match line.trim().split_once(char::is_whitespace) {
+        Some((program, arg)) => vec![program.to_string(), arg.trim().to_string()],
+        None => vec![line.trim().to_string()],
+    }
+}
+
+fn prepare_script_cmd(script: &str, args: &[String]) -> anyhow::Result<PreparedScript> {
+    // Create a temporary folder in which the script file will be
+    // created.
+    let tmp_dir = create_tmp_dir()?;
+    let file_path = tmp_dir.path().join("script.bat");
+
+    // Create the file and write into it.
+    // File is closed at the end of scope, which will allow to
+    // execute it after.
+    {
+        let mut tmp_file = File::create(&file_path)?;
+        tmp_file.write_all(b"@echo off\n")?;
+        tmp_file.write_all(script.as_bytes())?;
+        tmp_file.flush()?;
+    }
+
+    let mut command = process::Command::new(&file_path);
+    command.args(args);
+
+    Ok(PreparedScript {
+        tmp_dir,
+        file_path,
+        command,
+        keep_temp: false,
+        stdin: None,
+        interpreter: None,
+    })
+}
+
+fn prepare_script_powershell(script: &str, args: &[String]) -> anyhow::Result<PreparedScript> {
+    // Create a temporary folder in which the script file will be
+    // created.
+    let tmp_dir = create_tmp_dir()?;
+    let file_path = tmp_dir.path().join("script.ps1");
+
+    // Create the file and write into it.
+    // File is closed at the end of scope, which will allow to
+    // execute it after.
+    {
+        let mut tmp_file = File::create(&file_path)?;
+        tmp_file.write_all(script.as_bytes())?;
+        tmp_file.flush()?;
+    }
+
+    let powershell_cmd: Vec<_> = ["&".to_string(), format!("{}", file_path.display())]
+        .into_iter()
+        .chain(args.iter().map(|arg| snailquote::escape(arg).into_owned()))
+        .collect();
+
+    let powershell_cmd = powershell_cmd.join(" ");
+
+    let args = [
+        "-NoProfile",
+        "-ExecutionPolicy",
+        "Bypass",
+        "-Command",
+        &powershell_cmd,
+    ];
+
+    println!("{args:?}");
+
+    let mut command = process::Command::new("PowerShell");
+    command.args(args);
+
+    Ok(PreparedScript {
+        tmp_dir,
+        file_path,
+        command,
+        keep_temp: false,
+        stdin: None,
+        interpreter: None,
+    })
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    fn run_sh(script: &str, args: &[&str], stdin: &str) -> (process::ExitStatus, String, String) {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut stdout = Vec::new();
+        let mut stderr = Vec::new();
+
+        let status = execute_script_with_io(
+            script,
+            &args,
+            Shell::Sh,
+            stdin.as_bytes(),
+            &mut stdout,
+            &mut stderr,
+        )
+        .expect("script to run");
+
+        (
+            status,
+            String::from_utf8(stdout).unwrap(),
+            String::from_utf8(stderr).unwrap(),
+        )
+    }
+
+    #[test]
+    fn captures_stdout_and_stderr() {
+        let (status, stdout, stderr) = run_sh("echo Hello $1\necho Oops >&2", &["world"], "");
+
+        assert!(status.success());
+        assert_eq!(stdout, "Hello world\n");
+        assert_eq!(stderr, "Oops\n");
+    }
+
+    #[test]
+    fn feeds_stdin() {
+        let (status, stdout, _) = run_sh("cat", &[], "line 1\nline 2\n");
+
+        assert!(status.success());
+        assert_eq!(stdout, "line 1\nline 2\n");
+    }
+
+    #[test]
+    fn normalizes_crlf_line_endings() {
+        let (status, stdout, _) = run_sh("A=Hello\r\necho $A\r\n", &[], "");
+
+        assert!(status.success());
+        assert_eq!(stdout, "Hello\n");
+    }
+
+    #[test]
+    fn uses_script_shebang() {
+        let (status, stdout, _) = run_sh("#!/bin/cat\nHello", &[], "");
+
+        assert!(status.success());
+        assert_eq!(stdout, "#!/bin/cat\nHello");
+    }
+
+    #[test]
+    fn reads_shebang_interpreter() {
+        assert_eq!(shebang_interpreter("echo Hello"), ["/bin/sh"]);
+        assert_eq!(shebang_interpreter("#!/bin/bash\necho"), ["/bin/bash"]);
+        assert_eq!(
+            shebang_interpreter("#! /usr/bin/env python3 \nprint()"),
+            ["/usr/bin/env", "python3"]
+        );
+    }
+
+    #[test]
+    fn runs_non_executable_file_with_interpreter() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let prepared = PreparedScript::new("echo Hello $1", &["world".to_string()], Shell::Sh)
+            .expect("script to be prepared");
+        std::fs::set_permissions(&prepared.file_path, std::fs::Permissions::from_mode(0o400))
+            .unwrap();
+
+        let mut stdout = Vec::new();
+        let status = prepared
+            .run_with_io(io::empty(), &mut stdout, io::sink())
+            .expect("script to run");
+
+        assert!(status.success());
+        assert_eq!(String::from_utf8(stdout).unwrap(), "Hello world\n");
+    }
+
+    #[test]
+    fn returns_exit_code() {
+        let (status, _, _) = run_sh("exit 3", &[], "");
+
+        assert_eq!(status.code(), Some(3));
+    }
+}
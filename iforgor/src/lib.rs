@@ -1,23 +1,54 @@
+mod audit;
+mod config;
 pub mod ctrlc_handler;
+mod doctor;
+mod execute;
 mod on_disk;
+mod source_menu;
 
-pub use on_disk::OnDisk;
+pub use {
+    config::{Config, HistoryOrder},
+    execute::{execute_script, execute_script_with_io, PreparedScript},
+    on_disk::OnDisk,
+};
 
 use {
     anyhow::{anyhow, bail},
     serde::{Deserialize, Serialize},
     sha3::{Digest, Sha3_256},
     std::{
+        cell::{Cell, RefCell},
         collections::{BTreeMap, BTreeSet},
-        fs::File,
-        io::Write,
+        io::{Read, Write},
         path::{Path, PathBuf},
         process::{self},
+        time::{Duration, Instant},
     },
+    tap::Tap,
 };
 
 type CommandId = String;
 
+/// Environment variable set for scripts run by iforgor, used to detect
+/// recursive invocations.
+pub const RUNNING_ENV_VAR: &str = "IFORGOR_RUNNING";
+
+thread_local! {
+    /// Set while reloading sources from the menu, which messages would be
+    /// printed over.
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// `println!` for messages about loading sources, silenced by
+/// [`Registry::reload_quietly`].
+macro_rules! report {
+    ($($arg:tt)*) => {
+        if !QUIET.get() {
+            println!($($arg)*);
+        }
+    };
+}
+
 #[derive(clap::Parser, Debug)]
 #[command(version, about, long_about = None)]
 #[command(about = "The CLI tool for all those commands you forget about")]
@@ -35,44 +66,242 @@ pub struct Cli {
     #[arg(long)]
     registry_path: bool,
 
+    /// Display the history path.
+    #[arg(long)]
+    history_path: bool,
+
+    /// Display the paths of the iforgor folder, registry and history.
+    #[arg(long)]
+    paths: bool,
+
+    /// Print the id and name of each registered command, separated by a tab.
+    #[arg(long)]
+    list_ids: bool,
+
+    /// Print the trust id and name of each registered command, separated by
+    /// a tab, to add them to `trusted_ids` in the config.
+    #[arg(long)]
+    list_trust_ids: bool,
+
+    /// Open the registry in your editor, and check it is still valid once
+    /// the editor is closed.
+    #[arg(long)]
+    edit_registry: bool,
+
+    /// Order in which the history is displayed, overrides the config.
+    #[arg(long, value_enum)]
+    history_order: Option<HistoryOrder>,
+
+    /// Keep the temporary script files after execution and display their path,
+    /// which helps debugging scripts.
+    #[arg(long)]
+    keep_temp: bool,
+
+    /// Reload commands from sources before opening the menu.
+    #[arg(long)]
+    reload: bool,
+
+    /// Display the commands available in this directory (see `only_in_dir`
+    /// and `show_if`) instead of the current one. Commands still run in the
+    /// current directory.
+    #[arg(long, value_name = "PATH")]
+    filter_dir: Option<PathBuf>,
+
+    /// Don't add the commands which are run to the history.
+    #[arg(long)]
+    no_history: bool,
+
+    /// Print the result of commands run from the menu, `recent` or `run-tag`
+    /// on stderr in a stable `key=value` format, instead of the
+    /// human-friendly message.
+    #[arg(long)]
+    porcelain: bool,
+
+    /// Don't ask confirmation before purging files, or before running the
+    /// commands of `run-tag` (even the ones with a `confirm_message`).
+    #[arg(long)]
+    yes: bool,
+
+    /// When to use colors in the menu. `auto` uses colors in terminals,
+    /// unless the `NO_COLOR` environment variable is set.
+    #[arg(long, value_enum, default_value_t)]
+    color: ichoose::ColorChoice,
+
     #[command(subcommand)]
     command: Option<CliCommands>,
 }
 
 #[derive(clap::Subcommand, Debug)]
 pub enum CliCommands {
-    /// Source subcommands
+    /// Source subcommands, opens an interactive menu if none is provided
     Source {
         #[command(subcommand)]
-        inner: SourceCommands,
+        inner: Option<SourceCommands>,
     },
     /// Reload commands from sources.
     Reload,
+    /// List registered commands.
+    List {
+        /// Only list commands available in the current directory (see
+        /// `only_in_dir`).
+        #[arg(long)]
+        here: bool,
+    },
+    /// Check that iforgor files and sources are valid.
+    Doctor,
+    /// Run again the last command from the history, exits with its exit code.
+    #[command(alias = "!!")]
+    Recent,
+    /// Run all commands with a tag one after the other, stopping at the first
+    /// failure. Exits with the exit code of the failed command.
+    RunTag { tag: String },
+    /// Run a script file without registering it, exits with its exit code.
+    Exec {
+        /// Shell used to run the script.
+        #[arg(long, value_enum, default_value_t)]
+        shell: Shell,
+        /// Argument given to the script, can be repeated.
+        #[arg(long = "arg")]
+        args: Vec<String>,
+        /// Ask confirmation before running the script.
+        #[arg(long)]
+        risky: bool,
+        /// Script file, the script is read from stdin if omitted or "-".
+        file: Option<PathBuf>,
+    },
 }
 
 #[derive(clap::Subcommand, Debug)]
 pub enum SourceCommands {
     /// Add a source
-    Add { path: PathBuf },
+    Add {
+        /// Source file, picked among the `.toml` files of the current
+        /// directory if omitted.
+        #[arg(conflicts_with = "from_env")]
+        path: Option<PathBuf>,
+        /// Environment variable containing the TOML content of the source,
+        /// which is read again on each reload.
+        #[arg(long, value_name = "VAR")]
+        from_env: Option<String>,
+    },
     /// List all sources
-    List,
+    List {
+        /// Only display paths, one per line, to be used in scripts.
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Check a source file without registering it
+    Validate { path: PathBuf },
     /// Remove a source
-    Remove { path: PathBuf },
+    Remove {
+        /// Source file, picked among the registered sources if omitted.
+        #[arg(conflicts_with = "from_env")]
+        path: Option<PathBuf>,
+        /// Environment variable of a source added with `--from-env`.
+        #[arg(long, value_name = "VAR")]
+        from_env: Option<String>,
+    },
+    /// Move a source in the loading order, then reload commands
+    Move {
+        path: PathBuf,
+        /// New position of the source, starting from 1. Sources loaded later
+        /// override commands with the same id of earlier ones.
+        position: usize,
+    },
 }
 
 impl Cli {
-    pub fn run(self) -> anyhow::Result<()> {
-        let mut app_path = home::home_dir().ok_or(anyhow!("unable to fetch home dir"))?;
-        app_path.push(".iforgor");
-        let registry_path = app_path.join("registry.toml");
-        let history_path = app_path.join("history.toml");
+    pub fn run(mut self) -> anyhow::Result<()> {
+        let dirs = AppDirs::detect()?;
+        let registry_path = dirs.config.join("registry.toml");
+        let config_path = dirs.config.join("config.toml");
+
+        // An invalid config is only reported once needed, so that files can
+        // still be located, purged or checked.
+        let config = OnDisk::<Config>::open_or_default(config_path.clone());
+        let history_path = match config.as_ref().ok().and_then(|c| c.history_path.as_ref()) {
+            Some(path) => resolve_source_relative_path(path, Some(&config_path)),
+            None => dirs.state.join("history.toml"),
+        };
+        // The history path set in an invalid config is unknown.
+        let warn_history_path = || {
+            if let Err(e) = &config {
+                eprintln!(
+                    "⚠ The history path may be wrong as the config is invalid (run `iforgor \
+                    doctor` for details): {e}"
+                );
+            }
+        };
 
         if self.registry_path {
             println!("Registry path: {}", registry_path.display());
             return Ok(());
         }
 
+        if self.history_path {
+            warn_history_path();
+            println!("History path: {}", history_path.display());
+            return Ok(());
+        }
+
+        if self.paths {
+            warn_history_path();
+            if dirs.config == dirs.state {
+                println!("Folder path: {}", dirs.config.display());
+            } else {
+                println!("Config folder path: {}", dirs.config.display());
+                println!("State folder path: {}", dirs.state.display());
+            }
+            println!("Config path: {}", config_path.display());
+            println!("Registry path: {}", registry_path.display());
+            println!("History path: {}", history_path.display());
+            return Ok(());
+        }
+
+        if self.edit_registry {
+            return edit_registry(&registry_path);
+        }
+
+        if self.list_ids {
+            let registry = OnDisk::<Registry>::open_or_default(registry_path)?;
+            for (id, command) in &registry.commands {
+                println!("{id}\t{}", command.name);
+            }
+            return Ok(());
+        }
+
+        if self.list_trust_ids {
+            let registry = OnDisk::<Registry>::open_or_default(registry_path)?;
+            for command in registry.commands.values() {
+                println!("{}\t{}", command.trust_id(), command.name);
+            }
+            return Ok(());
+        }
+
+        // Purging the wrong file would lose data, the config must be fixed first.
+        if self.purge_all || self.purge_history {
+            if let Err(e) = &config {
+                bail!(
+                    "unable to purge as the config is invalid, the history path may be wrong \
+                    (run `iforgor doctor` for details): {e}"
+                );
+            }
+        }
+
         if self.purge_all {
+            println!(
+                "This will delete all registered sources and commands from \"{}\", and the \
+                history from \"{}\". Source files are not deleted.",
+                registry_path.display(),
+                history_path.display()
+            );
+            if !self.yes && !ask_confirmation("🗑️ Purge registry and history?")? {
+                println!("Purge aborted");
+                return Ok(());
+            }
+
+            print_backup(on_disk::backup_file(&registry_path)?);
+            print_backup(on_disk::backup_file(&history_path)?);
             OnDisk::<Registry>::new_from_default(registry_path).save()?;
             OnDisk::<History>::new_from_default(history_path).save()?;
 
@@ -81,168 +310,555 @@ impl Cli {
         }
 
         if self.purge_history {
+            println!(
+                "This will delete the history of ran commands from \"{}\".",
+                history_path.display()
+            );
+            if !self.yes && !ask_confirmation("🗑️ Purge history?")? {
+                println!("Purge aborted");
+                return Ok(());
+            }
+
+            print_backup(on_disk::backup_file(&history_path)?);
             OnDisk::<History>::new_from_default(history_path).save()?;
 
             println!("🗑️ Purged history!");
             return Ok(());
         }
 
+        if let Some(CliCommands::Doctor) = self.command {
+            return doctor::run(&dirs, &config_path, &registry_path, &history_path);
+        }
+
+        if let Some(CliCommands::Source {
+            inner: Some(SourceCommands::Validate { path }),
+        }) = &self.command
+        {
+            return doctor::validate_source(path);
+        }
+
+        let config = config?.into_inner();
+        let history_order = self.history_order.unwrap_or(config.history_order);
+        let completion_wait = config.completion_wait_secs.map(Duration::from_secs);
+        let highlight_color = config
+            .highlight_color
+            .as_deref()
+            .map(|color| {
+                color.parse::<ratatui::style::Color>().map_err(|_| {
+                    anyhow!(
+                        "invalid `highlight_color` \"{color}\" in \"{}\"",
+                        config_path.display()
+                    )
+                })
+            })
+            .transpose()?;
+
+        let run_options = RunOptions {
+            keep_temp: self.keep_temp,
+            audit_log: config.audit.then(|| dirs.state.join("audit.log")),
+            skip_risky_confirm: config.skip_risky_confirm,
+            skip_all_confirm: false,
+            trusted_ids: config.trusted_ids.clone(),
+        };
+
+        if let Some(CliCommands::Exec {
+            shell,
+            args,
+            risky,
+            file,
+        }) = self.command
+        {
+            return exec_script(shell, args, risky, file, &run_options);
+        }
+
         let mut registry = OnDisk::<Registry>::open_or_default(registry_path.clone())?;
         let mut history = OnDisk::<History>::open_or_default(history_path.clone())?;
 
-        let Some(command) = self.command else {
-            loop {
-                let current_dir =
-                    std::env::current_dir().expect("to be able to fetch current dir path");
+        let Some(command) = self.command.take() else {
+            refuse_nested_menu()?;
 
-                let commands: Vec<_> = registry
-                    .commands
-                    .iter()
-                    .filter(|(_, command)| filter_only_in_dir(&current_dir, command))
-                    .map(|(id, command)| ichoose::ListEntry {
-                        key: id.clone(),
-                        name: command.name.to_string(),
-                    })
-                    .collect();
+            if self.reload || config.reload_on_start {
+                registry.reload()?;
+                registry.save()?;
+            }
+
+            if registry.commands.is_empty() {
+                print_onboarding(&registry);
+                return Ok(());
+            }
+
+            warn_invalid_only_in_dir(&registry);
+
+            let show_if_cache = RefCell::new(ShowIfCache::default());
+            // Search is kept between runs of the same session.
+            let mut search = String::new();
+
+            loop {
+                let current_dir = filter_dir(self.filter_dir.as_deref())?;
+
+                let command_entries = |registry: &Registry| -> Vec<_> {
+                    registry
+                        .commands
+                        .iter()
+                        .filter(|(_, command)| {
+                            filter_only_in_dir(&current_dir, command, config.only_in_dir_subdirs)
+                        })
+                        .map(|(id, command)| command.list_entry(id))
+                        .collect()
+                };
+                let commands = command_entries(&registry);
+
+                // Registry reloaded with Ctrl+R, which replaces the current one
+                // once the menu is closed.
+                let reloaded = RefCell::new(None::<Registry>);
+                let reload = || {
+                    let mut new_registry = reloaded
+                        .borrow()
+                        .clone()
+                        .unwrap_or_else(|| Registry::clone(&registry));
+                    new_registry
+                        .reload_quietly()
+                        .map_err(|e| format!("Unable to reload sources: {e}"))?;
+
+                    let entries = command_entries(&new_registry);
+                    *reloaded.borrow_mut() = Some(new_registry);
+                    Ok(entries)
+                };
 
                 let history_list: Vec<_> = history
                     .history
                     .iter()
                     .filter_map(|id| registry.commands.get(id).map(|c| (id, c)))
-                    .filter(|(_, command)| filter_only_in_dir(&current_dir, command))
+                    .filter(|(_, command)| {
+                        filter_only_in_dir(&current_dir, command, config.only_in_dir_subdirs)
+                    })
                     .map(|(id, c)| ichoose::ListEntry {
-                        key: id.clone(),
-                        name: c.name.to_string(),
+                        note: history.last_runs.get(id).map(|run| run.summary(unix_now())),
+                        ..c.list_entry(id)
                     })
                     .collect();
 
-                let history_list: Vec<_> = history_list.into_iter().rev().collect();
+                // Checks running external programs are performed lazily by
+                // `ichoose` on entries matching the search.
+                let entry_filter = |entry: &ichoose::ListEntry<CommandId>| {
+                    let reloaded = reloaded.borrow();
+                    let registry = reloaded.as_ref().unwrap_or(&registry);
+                    registry.commands.get(&entry.key).is_some_and(|command| {
+                        filter_requires(command)
+                            && show_if_cache.borrow_mut().check(&current_dir, command)
+                    })
+                };
+
+                // History is stored oldest first.
+                let history_list: Vec<_> = match history_order {
+                    HistoryOrder::Newest => history_list.into_iter().rev().collect(),
+                    HistoryOrder::Oldest => history_list,
+                };
                 let history_list = if history_list.is_empty() {
                     None
                 } else {
                     Some(history_list.as_slice())
                 };
 
-                let choices: Vec<_> = ichoose::ListSearch {
-                    items: &commands,
+                let mut menu = ichoose::ListSearch {
+                    items: commands,
                     extra: ichoose::ListSearchExtra {
                         empty_search_list: history_list,
+                        entry_filter: Some(ichoose::EntryFilter(&entry_filter)),
+                        initial_search: search,
                         title: " iforgor ".to_string(),
                         text: "Run `iforgor help` to learn about subcommands. \
                             Search for multiple search terms by separating them with commas `,` \
                             Empty search displays history, type anything (including spaces) to \
                             display the filtered full list of commands."
                             .to_string(),
+                        actions: vec![
+                            ichoose::Action {
+                                key: EDIT_ACTION_KEY,
+                                name: "Edit".to_string(),
+                            },
+                            ichoose::Action {
+                                key: DELETE_ACTION_KEY,
+                                name: "Delete".to_string(),
+                            },
+                            ichoose::Action {
+                                key: VIEW_ACTION_KEY,
+                                name: "View".to_string(),
+                            },
+                        ],
+                        show_descriptions: true,
+                        queue: true,
+                        reload: Some(ichoose::Reload(&reload)),
+                        compact: config.compact_menu,
+                        count_label: Some("commands".to_string()),
+                        color: self.color,
                         ..Default::default()
                     },
+                };
+                if let Some(symbol) = &config.highlight_symbol {
+                    menu.extra.highlight_symbol = symbol.clone();
                 }
-                .run()?
-                .into_iter()
-                .collect();
-
-                if choices.is_empty() {
-                    break;
+                if let Some(color) = highlight_color {
+                    menu.extra.highlight_style = menu.extra.highlight_style.fg(color);
                 }
 
-                if choices.len() > 1 {
-                    bail!("Bug: There should be only one entry selected");
+                let outcome = menu.run_detailed()?;
+
+                if let Some(new_registry) = reloaded.into_inner() {
+                    *registry = new_registry;
+                    registry.save()?;
                 }
 
-                let choice = &choices[0];
+                search = outcome.search;
+
+                // Queued commands are run before the confirmed one.
+                let choices: Vec<_> = match outcome.entries.as_slice() {
+                    [] | [_] => outcome
+                        .queued
+                        .iter()
+                        .chain(&outcome.entries)
+                        .map(|entry| entry.key.clone())
+                        .collect(),
+                    _ => bail!("Bug: There should be only one entry selected"),
+                };
+                let Some(choice) = choices.last() else {
+                    break;
+                };
 
-                match registry.run_script_by_id(choice) {
-                    Err(e) => eprintln!("Encountered an error when running command: {e}"),
-                    Ok(status) => {
-                        match status.code() {
-                            Some(code) => {
-                                print!("\n🏁 Execution complete with code {code}, press Enter to proceed.")
+                if outcome.action == Some(EDIT_ACTION_KEY) {
+                    let source_path = registry
+                        .commands
+                        .get(choice)
+                        .and_then(|command| command.file_path());
+
+                    match source_path {
+                        Some(source_path) => {
+                            if let Err(e) = open_in_editor(&source_path) {
+                                eprintln!("Unable to edit source: {e}");
+                                wait_for_enter()?;
                             }
-                            None => print!(
-                                "\n🏁 Execution terminated by signal, press Enter to proceed."
-                            ),
+                            registry.reload()?;
+                            registry.save()?;
+                        }
+                        None => {
+                            println!("This command doesn't have a known source file.");
+                            wait_for_enter()?;
                         }
                     }
+
+                    continue;
                 }
 
-                std::io::stdout().flush()?;
-                let mut buf = String::new();
+                if outcome.action == Some(VIEW_ACTION_KEY) {
+                    if let Some(command) = registry.commands.get(choice) {
+                        if let Err(e) = view_in_pager(&command.script) {
+                            // Fallback to printing the script if the pager can't be used.
+                            println!(
+                                "Unable to start pager ({e}), script of \"{}\":\n",
+                                command.name
+                            );
+                            println!("{}", command.script);
+                            wait_for_enter()?;
+                        }
+                    }
 
-                // User may press Ctrl+C wanting to stop the script, but the execute finishes just before the press.
-                // Let's avoid killing iforgor in that situation.
-                ctrlc_handler::set_mode(ctrlc_handler::Mode::Ignore);
-                std::io::stdin().read_line(&mut buf)?;
-                ctrlc_handler::set_mode(ctrlc_handler::Mode::Kill);
+                    continue;
+                }
+
+                if outcome.action == Some(DELETE_ACTION_KEY) {
+                    if let Err(e) = registry.delete_command(choice) {
+                        eprintln!("Unable to delete command: {e}");
+                    }
+                    registry.save()?;
+                    wait_for_enter()?;
+
+                    continue;
+                }
 
-                println!("━━━━━━━━━━━━━━━");
+                for (i, choice) in choices.iter().enumerate() {
+                    let outcome = registry.run_script_by_id(choice, &run_options);
+
+                    if let Some(command) = registry.commands.get(choice) {
+                        self.record_run(
+                            &config,
+                            &mut history,
+                            &history_path,
+                            choice,
+                            command,
+                            outcome.as_ref().ok(),
+                        )?;
+                    }
+
+                    let success = outcome
+                        .as_ref()
+                        .is_ok_and(|outcome| outcome.status.is_none_or(|status| status.success()));
+                    let remaining = choices.len() - i - 1;
+                    let next = match remaining {
+                        0 => "press Enter to proceed".to_string(),
+                        _ if success => "running next queued command".to_string(),
+                        _ => format!(
+                            "skipping {remaining} queued command(s), press Enter to proceed"
+                        ),
+                    };
+
+                    match outcome {
+                        Err(e) => eprintln!("Encountered an error when running command: {e}"),
+                        Ok(_) if self.porcelain => (),
+                        Ok(RunOutcome { status: None, .. }) => {
+                            print!("\n🚀 Command started in a new terminal, {next}.")
+                        }
+                        Ok(RunOutcome {
+                            status: Some(status),
+                            duration,
+                        }) => {
+                            let duration = format_duration(duration);
+                            match status.code() {
+                                Some(code) => print!(
+                                    "\n🏁 Execution complete with code {code} in {duration}, {next}."
+                                ),
+                                None => print!(
+                                    "\n🏁 Execution terminated by signal after {duration}, {next}."
+                                ),
+                            }
+                        }
+                    }
 
-                // We can take it since we'll reload the registry from file
-                let mut modified_command = registry.commands.remove(choice);
+                    // We can take it since we'll reload the registry from file
+                    let mut modified_command = registry.commands.remove(choice);
 
-                // Reload files from disk in case multiple `iforgor` are running.
-                history = OnDisk::<History>::open(history_path.clone()).unwrap_or(history);
-                history.add_entry(choice);
-                history.save()?;
+                    // Reload the registry from disk in case multiple `iforgor`
+                    // are running.
+                    registry = OnDisk::open(registry_path.clone()).unwrap_or(registry);
+
+                    // update last command default args
+                    if let Some(in_file_command) = registry.commands.get_mut(choice) {
+                        if let Some(modified_command) = modified_command.take() {
+                            in_file_command.args_default = modified_command.args_default;
+                        }
+                    }
 
-                registry = OnDisk::open(registry_path.clone()).unwrap_or(registry);
+                    registry.save()?;
 
-                // update last command default args
-                if let Some(in_file_command) = registry.commands.get_mut(choice) {
-                    if let Some(modified_command) = modified_command.take() {     
-                        in_file_command.args_default = modified_command.args_default;
+                    if remaining == 0 || !success {
+                        break;
                     }
+                    println!("\n");
                 }
-                
-                registry.save()?;
+
+                wait_for_enter_or_timeout(completion_wait)?;
             }
 
             return Ok(());
         };
 
         match command {
+            CliCommands::Source { inner: None } => {
+                source_menu::run(&mut registry)?;
+            }
             CliCommands::Source {
-                inner: SourceCommands::Add { path },
+                inner: Some(SourceCommands::Add { path, from_env }),
+            } => match (path, from_env) {
+                (_, Some(var)) => registry.add_env_source(var)?,
+                (Some(path), None) => registry.add_source(path)?,
+                (None, None) => source_menu::add_sources(&mut registry)?,
+            },
+            CliCommands::Source {
+                inner: Some(SourceCommands::List { raw }),
             } => {
-                let path = std::fs::canonicalize(path)?;
-                println!("Adding source \"{}\"", path.display());
+                for source in &registry.sources {
+                    if raw {
+                        println!("{}", source.display());
+                        continue;
+                    }
+
+                    if !source.exists() {
+                        println!("{} (missing)", source.display());
+                        continue;
+                    }
+
+                    let Ok(content) = CommandsSource::read(source) else {
+                        println!(
+                            "{} (invalid, run `iforgor doctor` for details)",
+                            source.display()
+                        );
+                        continue;
+                    };
+
+                    let count = registry
+                        .commands
+                        .values()
+                        .filter(|command| command.source_path.as_ref() == Some(source))
+                        .count();
+
+                    match &content.name {
+                        Some(name) => {
+                            println!("{name} ({}): {count} command(s)", source.display())
+                        }
+                        None => println!("{}: {count} command(s)", source.display()),
+                    }
 
-                load_scripts_for_source(&mut registry.commands, path.clone())?;
+                    if let Some(description) = &content.description {
+                        println!("  {description}");
+                    }
+                }
 
-                registry.sources.insert(path);
+                // Only paths are displayed in raw mode.
+                for var in registry.env_sources.iter().filter(|_| !raw) {
+                    if std::env::var_os(var).is_some() {
+                        println!("${var} (environment variable)");
+                    } else {
+                        println!("${var} (environment variable, not set)");
+                    }
+                }
             }
             CliCommands::Source {
-                inner: SourceCommands::List,
+                inner: Some(SourceCommands::Remove { path, from_env }),
             } => {
-                for source in &registry.sources {
-                    println!("{}", source.display());
+                match (path, from_env) {
+                    (_, Some(var)) => registry.remove_env_source(&var)?,
+                    (Some(path), None) => registry.remove_source(path)?,
+                    // Commands are reloaded after picking sources.
+                    (None, None) => {
+                        source_menu::remove_sources(&mut registry)?;
+                        registry.save()?;
+                        return Ok(());
+                    }
                 }
+                println!(
+                    "Commands in that source are still registred. Run \
+                    `iforgor reload` to reload commands from remaining sources only"
+                );
             }
             CliCommands::Source {
-                inner: SourceCommands::Remove { path },
+                inner: Some(SourceCommands::Move { path, position }),
+            } => {
+                registry.move_source(path, position)?;
+            }
+            CliCommands::Doctor
+            | CliCommands::Exec { .. }
+            | CliCommands::Source {
+                inner: Some(SourceCommands::Validate { .. }),
             } => {
-                // try to remove raw path, this allow to delete sources that no
-                // longer exist on disk
-                if !registry.sources.remove(&path) {
-                    let path = std::fs::canonicalize(path)?;
+                unreachable!("handled before loading the registry")
+            }
+            CliCommands::Reload => {
+                registry.reload()?;
+            }
+            CliCommands::Recent => {
+                let Some(id) = history.history.last().cloned() else {
+                    bail!("History is empty, run a command from the menu first");
+                };
+                let Some(command) = registry.commands.get(&id).cloned() else {
+                    bail!("The last command ran is no longer registered");
+                };
 
-                    if !registry.sources.remove(&path) {
-                        bail!("Path was not a registered source");
-                    }
+                let outcome = registry.run_script_by_id(&id, &run_options)?;
+                registry.save()?;
+                self.record_run(
+                    &config,
+                    &mut history,
+                    &history_path,
+                    &id,
+                    &command,
+                    Some(&outcome),
+                )?;
+
+                let code = outcome
+                    .status
+                    .map_or(0, |status| status.code().unwrap_or(1));
+                process::exit(code);
+            }
+            CliCommands::RunTag { tag } => {
+                let current_dir = filter_dir(self.filter_dir.as_deref())?;
+                let mut show_if_cache = ShowIfCache::default();
+
+                let mut entries: Vec<_> = registry
+                    .commands
+                    .iter()
+                    .filter(|(_, command)| {
+                        command.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag))
+                    })
+                    .filter(|(_, command)| {
+                        // Same filters as the menu.
+                        let available =
+                            filter_only_in_dir(&current_dir, command, config.only_in_dir_subdirs)
+                                && filter_requires(command)
+                                && show_if_cache.check(&current_dir, command);
+                        if !available {
+                            println!(
+                                "⚠ Skipping \"{}\", which isn't available in this directory \
+                                (see `only_in_dir`, `requires` and `show_if`)",
+                                command.name
+                            );
+                        }
+                        available
+                    })
+                    .map(|(id, command)| command.list_entry(id))
+                    .collect();
+                if entries.is_empty() {
+                    bail!("No command tagged \"{tag}\" is available in this directory");
+                }
+                ichoose::sort_entries(&mut entries);
+
+                let mut run_options = run_options.clone();
+                if self.yes {
+                    run_options.skip_all_confirm = true;
+                }
+
+                let count = entries.len();
+                for (i, id) in entries.iter().map(|entry| &entry.key).enumerate() {
+                    let command = registry.commands[id].clone();
+                    println!("▶ [{}/{count}] {}", i + 1, command.name);
+
+                    let outcome = registry.run_script_by_id(id, &run_options)?;
+                    registry.save()?;
+                    self.record_run(
+                        &config,
+                        &mut history,
+                        &history_path,
+                        id,
+                        &command,
+                        Some(&outcome),
+                    )?;
+
+                    let duration = format_duration(outcome.duration);
+                    let status = match outcome.status {
+                        Some(status) if !status.success() => status,
+                        _ => {
+                            println!("\n🏁 \"{}\" completed in {duration}\n", command.name);
+                            continue;
+                        }
+                    };
 
-                    println!("Removed source \"{}\"", path.display());
+                    let remaining = count - i - 1;
                     println!(
-                        "Commands in that source are still registred. Run \
-                    `iforgor reload` to reload commands from remaining sources only"
+                        "\n🏁 \"{}\" failed ({status}) in {duration}, skipping {remaining} \
+                        remaining command(s)",
+                        command.name
                     );
+                    process::exit(status.code().unwrap_or(1));
                 }
             }
-            CliCommands::Reload => {
-                let mut commands = BTreeMap::new();
+            CliCommands::List { here } => {
+                let current_dir = filter_dir(self.filter_dir.as_deref())?;
 
-                for path in &registry.sources {
-                    load_scripts_for_source(&mut commands, path.clone())?;
-                }
+                let mut commands: Vec<_> = registry
+                    .commands
+                    .values()
+                    .filter(|command| {
+                        !here
+                            || filter_only_in_dir(&current_dir, command, config.only_in_dir_subdirs)
+                    })
+                    .collect();
+                commands.sort_by_key(|command| &command.name);
+
+                for command in commands {
+                    println!("{}", command.display_name());
 
-                registry.commands = commands;
+                    if let Some(description) = &command.description {
+                        println!("  {description}");
+                    }
+                }
             }
         }
 
@@ -253,123 +869,1665 @@ impl Cli {
     }
 }
 
-fn load_scripts_for_source(
-    commands: &mut BTreeMap<CommandId, UserCommand>,
-    path: PathBuf,
-) -> anyhow::Result<()> {
-    println!("Loading source: {}", path.display());
-    let scripts = OnDisk::<CommandsSource>::open(path.clone())?.into_inner();
+impl Cli {
+    /// Report the outcome of a command: run the completion hook, print the
+    /// porcelain line, and add the run to the history (read again from disk
+    /// in case multiple `iforgor` are running). `outcome` is `None` if the
+    /// command couldn't run.
+    fn record_run(
+        &self,
+        config: &Config,
+        history: &mut OnDisk<History>,
+        history_path: &Path,
+        id: &CommandId,
+        command: &UserCommand,
+        outcome: Option<&RunOutcome>,
+    ) -> anyhow::Result<()> {
+        if let Some(outcome) = outcome {
+            if let Some(status) = outcome.status {
+                run_completion_hook(config, &command.name, status);
+            }
 
-    for script in scripts.entries {
-        // Ignore scripts incompatible with current platform.
-        match script.only_on {
-            Some(Platform::Windows) if !cfg!(target_os = "windows") => continue,
-            Some(Platform::Linux) if !cfg!(target_os = "linux") => continue,
-            _ => (),
+            if self.porcelain {
+                eprintln!("{}", outcome.porcelain(id, &command.name));
+            }
         }
 
-        let id = script.generate_id();
-        println!("- Added command: {}", script.name);
-        commands.insert(id, script);
+        if self.no_history || command.no_history {
+            return Ok(());
+        }
+
+        if let Ok(on_disk) = OnDisk::<History>::open(history_path.to_path_buf()) {
+            *history = on_disk;
+        }
+        let status = outcome.and_then(|outcome| outcome.status);
+        history.add_run(id, status.map(|status| status.success()));
+        history.save()
     }
+}
 
+/// Fail when running inside a script launched by iforgor, as opening an
+/// interactive menu would nest it in the current one.
+pub(crate) fn refuse_nested_menu() -> anyhow::Result<()> {
+    if std::env::var_os(RUNNING_ENV_VAR).is_some() {
+        bail!(
+            "Running inside a script launched by iforgor, opening an interactive menu would \
+            nest it in the current one. Use a subcommand instead (see `iforgor help`)"
+        );
+    }
     Ok(())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct History {
-    pub history: Vec<CommandId>,
+/// Wait for the user to press Enter, after the output of an action has been
+/// displayed.
+pub(crate) fn wait_for_enter() -> std::io::Result<()> {
+    std::io::stdout().flush()?;
+    let mut buf = String::new();
+
+    // User may press Ctrl+C wanting to stop the script, but the execute finishes just before the press.
+    // Let's avoid killing iforgor in that situation.
+    ctrlc_handler::set_mode(ctrlc_handler::Mode::Ignore);
+    std::io::stdin().read_line(&mut buf)?;
+    ctrlc_handler::set_mode(ctrlc_handler::Mode::Kill);
+
+    println!("━━━━━━━━━━━━━━━");
+
+    Ok(())
 }
 
-impl History {
-    pub fn add_entry(&mut self, id: &CommandId) {
-        let mut alt = Vec::new();
-        std::mem::swap(&mut alt, &mut self.history);
+/// Run the `on_success` or `on_failure` snippet of the config once a command
+/// completed. Failures are only reported.
+fn run_completion_hook(config: &Config, name: &str, status: process::ExitStatus) {
+    let hook = if status.success() {
+        &config.on_success
+    } else {
+        &config.on_failure
+    };
 
-        self.history = alt.into_iter().filter(|hid| hid != id).collect();
-        self.history.push(id.clone());
+    let Some(hook) = hook else {
+        return;
+    };
+
+    let code = status
+        .code()
+        .map(|code| code.to_string())
+        .unwrap_or_default();
+    let hook_status = PreparedScript::new(hook, &[], Shell::default()).and_then(|hook| {
+        hook.envs([("IFORGOR_CMD_NAME", name), ("IFORGOR_EXIT_CODE", &code)])
+            .run()
+    });
+
+    match hook_status {
+        Ok(hook_status) if !hook_status.success() => {
+            println!("⚠ Completion hook failed ({hook_status})")
+        }
+        Ok(_) => (),
+        Err(e) => println!("⚠ Unable to run completion hook: {e}"),
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct Registry {
-    pub sources: BTreeSet<PathBuf>,
-    pub commands: BTreeMap<CommandId, UserCommand>,
+fn print_backup(backup: Option<PathBuf>) {
+    if let Some(backup) = backup {
+        println!("💾 Backup saved to \"{}\"", backup.display());
+    }
 }
 
-impl Registry {
-    pub fn run_script_by_id(&mut self, id: &CommandId) -> anyhow::Result<process::ExitStatus> {
-        let Some(entry) = self.commands.get_mut(id) else {
-            bail!("Unknown command ID {id}")
-        };
-
-        let UserCommand {
-            name,
-            script,
-            args,
-            args_default,
-            shell,
-            risky,
-            ..
-        } = entry;
+/// Ask a yes/no question, which defaults to no if the answer is empty.
+fn ask_confirmation(question: &str) -> std::io::Result<bool> {
+    print!("{question} [y/N]: ");
+    let mut buf = String::new();
+    std::io::stdout().flush()?;
+    std::io::stdin().read_line(&mut buf)?;
 
-        let mut args_values = Vec::new();
-        if !args.is_empty() {
-            println!(
-"Default values or previously used values will be suggested, type nothing \
-and press Enter to use it, or type anything (even a space) to discard it.
+    Ok(["y", "yes"].contains(&buf.to_lowercase().trim()))
+}
 
-This script requires the following arguments (use Ctrl+C to abort execution):\n"
-            );            
-        }
+/// Format a duration for humans, such as `4.2s` or `2m13s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
 
-        for (i, arg) in args.iter().enumerate() {
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, _) => format!("{:.1}s", duration.as_secs_f64()),
+        (0, minutes, secs) => format!("{minutes}m{secs:02}s"),
+        (hours, minutes, secs) => format!("{hours}h{minutes:02}m{secs:02}s"),
+    }
+}
 
-            let mut buf = String::new();
-            
-            if let Some(def) = args_default.get(i).filter(|def| !def.trim().is_empty()) {
-                println!("- {arg} [Default: {def}]:");
-                std::io::stdin().read_line(&mut buf)?;
-                if buf.trim_end_matches(|c| c == '\n' || c == '\r').is_empty() {
-                    println!("Using default!");
-                    buf = def.clone();
-                }
-            } else {
-                print!("- {arg}: ");
-                std::io::stdout().flush()?;
-                std::io::stdin().read_line(&mut buf)?;
-            }
+/// Wait for the user to press Enter like [`wait_for_enter`], but proceed
+/// automatically once `timeout` elapsed. Without a terminal the timeout is
+/// ignored.
+fn wait_for_enter_or_timeout(timeout: Option<Duration>) -> std::io::Result<()> {
+    use ratatui::crossterm::{terminal, tty::IsTty};
 
-            args_values.push(buf.trim().to_string());
-        }
+    let Some(timeout) = timeout.filter(|_| std::io::stdin().is_tty()) else {
+        return wait_for_enter();
+    };
 
-        if *risky {
-            print!("⚠️ Script is tagged as risky, are you sure you want to run it? [y/N]: ");
-            let mut buf = String::new();
-            std::io::stdout().flush()?;
-            std::io::stdin().read_line(&mut buf)?;
+    print!(" (continuing in {}s)", timeout.as_secs());
+    std::io::stdout().flush()?;
 
-            if !["y", "yes"].contains(&buf.to_lowercase().trim()) {
-                bail!("Aborted execution of risky script")
-            }
-        }
+    // Raw mode allows to get the key press without blocking on a whole line.
+    let deadline = Instant::now() + timeout;
+    terminal::enable_raw_mode()?;
+    let res = wait_for_enter_key(deadline);
+    terminal::disable_raw_mode()?;
+    res?;
 
-        println!("💭 Running \"{name}\" with shell \"{shell:?}\"\n");
+    println!("\n━━━━━━━━━━━━━━━");
 
-        ctrlc_handler::set_mode(ctrlc_handler::Mode::Ignore);
-        let status = execute_script(script, &args_values, *shell)?;
-        ctrlc_handler::set_mode(ctrlc_handler::Mode::Kill);
+    Ok(())
+}
 
-        // Set used values as new default.
-        *args_default = args_values;
+fn wait_for_enter_key(deadline: Instant) -> std::io::Result<()> {
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
 
-        Ok(status)
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || !event::poll(remaining)? {
+            return Ok(());
+        }
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && key.code == KeyCode::Enter {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Count down before running a command, returning `false` if the user
+/// pressed Esc (or Ctrl+C) to abort. Without a terminal keys can't be read,
+/// so it only waits.
+fn countdown(duration: Duration) -> std::io::Result<bool> {
+    use ratatui::crossterm::{terminal, tty::IsTty};
+
+    let deadline = Instant::now() + duration;
+    let is_tty = std::io::stdin().is_tty();
+    if is_tty {
+        terminal::enable_raw_mode()?;
+    }
+
+    let res = (|| loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(true);
+        }
+
+        print!(
+            "\r⏳ Running in {}s, press Esc to abort ",
+            remaining.as_secs_f64().ceil()
+        );
+        std::io::stdout().flush()?;
+
+        // Wake up at least every second to update the countdown.
+        let step = remaining.min(Duration::from_secs(1));
+        if !is_tty {
+            std::thread::sleep(step);
+        } else if wait_for_abort_key(Instant::now() + step)? {
+            return Ok(false);
+        }
+    })();
+
+    if is_tty {
+        terminal::disable_raw_mode()?;
+    }
+    println!();
+
+    res
+}
+
+/// Wait until `deadline`, returning `true` if Esc or Ctrl+C was pressed.
+fn wait_for_abort_key(deadline: Instant) -> std::io::Result<bool> {
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || !event::poll(remaining)? {
+            return Ok(false);
+        }
+
+        if let Event::Key(key) = event::read()? {
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if key.kind == KeyEventKind::Press && (key.code == KeyCode::Esc || ctrl_c) {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+/// Home directory of the user, with fallbacks on environment variables for
+/// environments in which it can't be detected normally.
+pub fn home_dir() -> Option<PathBuf> {
+    home::home_dir()
+        .or_else(|| std::env::var_os("HOME").map(PathBuf::from))
+        .or_else(|| std::env::var_os("USERPROFILE").map(PathBuf::from))
+        .filter(|path| !path.as_os_str().is_empty())
+}
+
+/// Folder in which iforgor stores all its files if it exists: `~/.iforgor`,
+/// or the content of `IFORGOR_HOME` if the home directory is unknown.
+pub fn app_dir() -> anyhow::Result<PathBuf> {
+    if let Some(home) = home_dir() {
+        return Ok(home.join(".iforgor"));
+    }
+
+    match std::env::var_os("IFORGOR_HOME").filter(|path| !path.is_empty()) {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => bail!(
+            "unable to fetch home dir, set `IFORGOR_HOME` to the folder in which iforgor \
+            should store its files"
+        ),
+    }
+}
+
+/// Folders in which iforgor stores its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppDirs {
+    /// Folder of the config and registry.
+    pub config: PathBuf,
+    /// Folder of the history and audit log.
+    pub state: PathBuf,
+}
+
+impl AppDirs {
+    /// Uses the folder from [`app_dir`] if it exists, to keep using the files
+    /// of existing installs. Otherwise `$XDG_CONFIG_HOME/iforgor` and
+    /// `$XDG_STATE_HOME/iforgor` are used, defaulting to `~/.config/iforgor`
+    /// and `~/.local/state/iforgor` when the variables are not set. The
+    /// folder from [`app_dir`] is only used for those which are unknown.
+    pub fn detect() -> anyhow::Result<Self> {
+        let app_dir = app_dir();
+
+        if let Some(app_dir) = app_dir.as_ref().ok().filter(|app_dir| app_dir.exists()) {
+            return Ok(Self {
+                config: app_dir.clone(),
+                state: app_dir.clone(),
+            });
+        }
+
+        // The XDG spec requires paths to be absolute, others are ignored like
+        // unset variables.
+        let home = home_dir();
+        let xdg_dir = |var: &str, default: &[&str]| {
+            std::env::var_os(var)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+                .or_else(|| {
+                    let default: PathBuf = default.iter().collect();
+                    home.as_ref().map(|home| home.join(default))
+                })
+                .map(|path| path.join("iforgor"))
+        };
+
+        match (
+            xdg_dir("XDG_CONFIG_HOME", &[".config"]),
+            xdg_dir("XDG_STATE_HOME", &[".local", "state"]),
+        ) {
+            (Some(config), Some(state)) => Ok(Self { config, state }),
+            (config, state) => {
+                let app_dir = app_dir?;
+                Ok(Self {
+                    config: config.unwrap_or_else(|| app_dir.clone()),
+                    state: state.unwrap_or(app_dir),
+                })
+            }
+        }
+    }
+}
+
+/// Run a script file (or stdin) as a transient command, then exit with its
+/// exit code.
+fn exec_script(
+    shell: Shell,
+    args: Vec<String>,
+    risky: bool,
+    file: Option<PathBuf>,
+    options: &RunOptions,
+) -> anyhow::Result<()> {
+    let (name, script) = match file.filter(|file| file.as_os_str() != "-") {
+        Some(file) => {
+            let script = std::fs::read_to_string(&file)
+                .map_err(|e| anyhow!("unable to read \"{}\": {e}", file.display()))?;
+            (file.display().to_string(), script)
+        }
+        None => {
+            // The confirmation would be read from stdin once exhausted.
+            if risky && !options.skip_risky_confirm {
+                bail!(
+                    "risky scripts can't be read from stdin, as their confirmation is read from \
+                    it too. Write the script in a file instead"
+                );
+            }
+
+            let mut script = String::new();
+            std::io::stdin().read_to_string(&mut script)?;
+            ("stdin".to_string(), script)
+        }
+    };
+
+    let command = UserCommand {
+        name,
+        script,
+        shell,
+        risky,
+        ..Default::default()
+    };
+
+    let status = command
+        .run_with_args(&args, options)?
+        .status
+        .expect("transient command is not detached");
+
+    process::exit(status.code().unwrap_or(1));
+}
+
+/// Open a file in the user's editor (`VISUAL` or `EDITOR`, which can contain
+/// options) and wait for it to be closed.
+fn open_in_editor(path: &Path) -> anyhow::Result<()> {
+    let default_editor = if cfg!(target_os = "windows") {
+        "notepad"
+    } else {
+        "vi"
+    };
+
+    let editor = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| default_editor.to_string());
+
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().expect("editor to not be empty");
+    let status = process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| anyhow!("unable to start editor \"{program}\": {e}"))?;
+
+    if !status.success() {
+        bail!("editor exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Display a text in the user's pager (`PAGER`, which can contain options)
+/// and wait for it to be closed.
+fn view_in_pager(text: &str) -> anyhow::Result<()> {
+    let default_pager = if cfg!(target_os = "windows") {
+        "more"
+    } else {
+        "less"
+    };
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| default_pager.to_string());
+
+    let mut parts = pager.split_whitespace();
+    let program = parts.next().expect("pager to not be empty");
+    let mut child = process::Command::new(program)
+        .args(parts)
+        .stdin(process::Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("unable to start pager \"{program}\": {e}"))?;
+
+    // The pager may exit before reading everything, which is fine.
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(text.as_bytes());
+    }
+
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("pager exited with {status}");
+    }
+
+    Ok(())
+}
+
+/// Open the registry in the user's editor, offering to restore its previous
+/// content if it can't be parsed once the editor is closed.
+fn edit_registry(path: &Path) -> anyhow::Result<()> {
+    if !path.exists() {
+        OnDisk::<Registry>::new_from_default(path.to_path_buf()).save()?;
+    }
+
+    let previous = std::fs::read_to_string(path)?;
+    open_in_editor(path)?;
+
+    let Err(e) = OnDisk::<Registry>::open(path.to_path_buf()) else {
+        println!("✓ Registry is valid");
+        return Ok(());
+    };
+
+    println!("⚠ Registry can't be parsed: {e}");
+    if ask_confirmation("Restore the registry as it was before editing?")? {
+        std::fs::write(path, previous)?;
+        println!("Registry restored");
+    } else {
+        println!("Edit it again, or run `iforgor --purge-all` to reset it");
+    }
+
+    Ok(())
+}
+
+/// Explains how to add commands, displayed instead of an empty menu.
+fn print_onboarding(registry: &Registry) {
+    if registry.sources.is_empty() && registry.env_sources.is_empty() {
+        println!("👋 Welcome to iforgor! No source of commands is registered yet.");
+    } else {
+        println!(
+            "No commands available: the registered sources don't contain any command \
+            (or commands for this platform). Use `iforgor source list` to see them."
+        );
+    }
+
+    println!(
+        r#"
+Commands are loaded from TOML source files, such as:
+
+    [[entries]]
+    name = "Say hello"
+    script = "echo Hello $1"
+    args = ["name"]
+
+Register a source file with:
+
+    iforgor source add <PATH>
+
+Then run `iforgor` again to pick a command. See the README for all available fields."#
+    );
+}
+
+/// Add the commands of a source to `commands`, replacing the ones with the
+/// same id of previous sources. Replaced commands of source files are moved
+/// to `overridden`.
+fn merge_commands(
+    commands: &mut BTreeMap<CommandId, UserCommand>,
+    overridden: &mut Vec<UserCommand>,
+    source_commands: BTreeMap<CommandId, UserCommand>,
+) {
+    for (id, command) in source_commands {
+        if let Some(previous) = commands.insert(id, command) {
+            if previous.source_path.is_some() {
+                overridden.push(previous);
+            }
+        }
+    }
+}
+
+/// Names shared by several commands, with the sources defining them (the
+/// name of the file, or `env` for environment variables).
+fn duplicate_names(commands: &BTreeMap<CommandId, UserCommand>) -> BTreeMap<&str, Vec<String>> {
+    let mut names = BTreeMap::<_, Vec<_>>::new();
+    for command in commands.values() {
+        let source = command
+            .source_path
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map_or("env".into(), |name| name.to_string_lossy().into_owned());
+        names.entry(command.name.as_str()).or_default().push(source);
+    }
+
+    names.retain(|_, sources| sources.len() > 1);
+    names
+}
+
+/// Warn about commands sharing the same name, which can't be told apart in
+/// the menu besides their source.
+fn warn_duplicate_names(commands: &BTreeMap<CommandId, UserCommand>) {
+    for (name, sources) in duplicate_names(commands) {
+        report!(
+            "⚠ {} commands are named \"{name}\" (from {}), consider renaming them",
+            sources.len(),
+            sources.join(", ")
+        );
+    }
+}
+
+/// Load the commands of a source into `commands`. Returns `false` if the
+/// source was skipped as it requires a newer version of iforgor.
+fn load_scripts_for_source(
+    commands: &mut BTreeMap<CommandId, UserCommand>,
+    path: PathBuf,
+) -> anyhow::Result<bool> {
+    report!("Loading source: {}", path.display());
+    let scripts = CommandsSource::read(&path)?;
+
+    if scripts.entries.is_empty() {
+        if path.is_dir() {
+            report!("⚠ Folder \"{}\" doesn't contain any script", path.display());
+        } else {
+            warn_no_entries(&format!("\"{}\"", path.display()));
+        }
+    }
+
+    Ok(load_commands(commands, scripts, Some(&path)))
+}
+
+/// Load the commands of a source defined in an environment variable into
+/// `commands`. Returns `false` if the variable is not set or the source was
+/// skipped.
+fn load_scripts_for_env_source(
+    commands: &mut BTreeMap<CommandId, UserCommand>,
+    var: &str,
+) -> anyhow::Result<bool> {
+    report!("Loading source from environment variable: {var}");
+
+    let Ok(content) = std::env::var(var) else {
+        report!("⚠ Environment variable is not set, the source is skipped");
+        return Ok(false);
+    };
+
+    let scripts = CommandsSource::from_toml(&content)
+        .map_err(|e| anyhow!("invalid source in environment variable {var}: {e}"))?;
+
+    if scripts.entries.is_empty() {
+        warn_no_entries(&format!("environment variable {var}"));
+    }
+
+    Ok(load_commands(commands, scripts, None))
+}
+
+/// Warn that a source doesn't define any command, which is likely a mistake
+/// in the name of its tables.
+fn warn_no_entries(source: &str) {
+    report!(
+        "⚠ Source {source} doesn't define any command, they must be written in `[[entries]]` \
+        tables"
+    );
+}
+
+/// Load the commands of a parsed source into `commands`. Returns `false` if
+/// the source was skipped as it requires a newer version of iforgor.
+fn load_commands(
+    commands: &mut BTreeMap<CommandId, UserCommand>,
+    scripts: CommandsSource,
+    path: Option<&Path>,
+) -> bool {
+    if let Some(min_version) = &scripts.min_version {
+        let installed = env!("CARGO_PKG_VERSION");
+        match is_version_older(installed, min_version) {
+            Some(false) => (),
+            Some(true) => {
+                report!(
+                    "⚠ Source requires iforgor {min_version} or newer (installed: {installed}), \
+                     its commands are skipped"
+                );
+                return false;
+            }
+            None => report!("⚠ Ignoring invalid min_version \"{min_version}\""),
+        }
+    }
+
+    for mut script in scripts.entries {
+        // Ignore scripts incompatible with current platform.
+        if !script.is_compatible_with_current_platform() {
+            continue;
+        }
+
+        if script.script.trim().is_empty() {
+            report!("⚠ Skipping \"{}\" as its script is empty", script.name);
+            continue;
+        }
+
+        if let Some(hotkey) = script.hotkey.filter(|hotkey| !hotkey.is_ascii_digit()) {
+            report!(
+                "⚠ Ignoring hotkey '{hotkey}' of \"{}\", only digits are allowed",
+                script.name
+            );
+            script.hotkey = None;
+        }
+
+        let ignored = script.ignored_when_detached();
+        if !ignored.is_empty() {
+            report!(
+                "⚠ Ignoring `{}` of \"{}\" as it runs in a new terminal (`detach`)",
+                ignored.join("`, `"),
+                script.name
+            );
+        }
+
+        let id = script.generate_id();
+        report!("- Added command: {}", script.name);
+        commands.insert(
+            id,
+            UserCommand {
+                source_path: path.map(Path::to_path_buf),
+                ..script
+            },
+        );
+    }
+
+    true
+}
+
+/// Hash of the content of a source file, used to detect if it changed.
+fn hash_source(path: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha3_256::new();
+
+    if path.is_dir() {
+        // Names are hashed too, as they are the names of the commands.
+        for file in script_files(path)? {
+            hasher.update(file.file_name().unwrap_or_default().as_encoded_bytes());
+            hasher.update([0]);
+            hasher.update(std::fs::read(&file)?);
+            hasher.update([0]);
+        }
+    } else {
+        let content = std::fs::read(path)?;
+        hasher.update(&content);
+
+        // Sources are loaded again when the overrides matching the current
+        // host or environment change.
+        for condition in matching_override_conditions(&content) {
+            hasher.update([0]);
+            hasher.update(condition);
+        }
+    }
+
+    Ok(base16ct::lower::encode_string(&hasher.finalize()))
+}
+
+/// Whether the condition of an entry override, `host:NAME` or `env:VAR=VALUE`,
+/// matches the current host or environment.
+fn override_matches(condition: &str) -> anyhow::Result<bool> {
+    if let Some(host) = condition.strip_prefix("host:") {
+        return Ok(hostname().is_some_and(|hostname| hostname.eq_ignore_ascii_case(host)));
+    }
+
+    if let Some((var, value)) = condition
+        .strip_prefix("env:")
+        .and_then(|env| env.split_once('='))
+    {
+        return Ok(std::env::var(var).is_ok_and(|current| current == value));
+    }
+
+    bail!("invalid override condition \"{condition}\", expected `host:NAME` or `env:VAR=VALUE`")
+}
+
+/// Conditions of the overrides of a source content which currently match.
+fn matching_override_conditions(content: &[u8]) -> Vec<String> {
+    let Some(source) = std::str::from_utf8(content)
+        .ok()
+        .and_then(|content| toml::from_str::<toml::Table>(content).ok())
+    else {
+        return Vec::new();
+    };
+
+    source
+        .get("entries")
+        .and_then(|entries| entries.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("overrides")?.as_table())
+        .flat_map(|overrides| overrides.keys())
+        .filter(|condition| override_matches(condition).unwrap_or(false))
+        .cloned()
+        .collect()
+}
+
+/// Name of the machine, used by `host:NAME` overrides.
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+/// Files of a folder source, sorted by name. Hidden files and subfolders are
+/// ignored.
+fn script_files(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+
+        if path.is_file() && !hidden {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct History {
+    pub history: Vec<CommandId>,
+    /// Last run of each command of the history.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub last_runs: BTreeMap<CommandId, LastRun>,
+}
+
+impl History {
+    pub fn add_entry(&mut self, id: &CommandId) {
+        let mut alt = Vec::new();
+        std::mem::swap(&mut alt, &mut self.history);
+
+        self.history = alt.into_iter().filter(|hid| hid != id).collect();
+        self.history.push(id.clone());
+
+        // Runs of commands no longer in the history (such as after editing
+        // the history file) are dropped.
+        let history = &self.history;
+        self.last_runs.retain(|id, _| history.contains(id));
+    }
+
+    /// Add a command to the history, recording when it ran and whether it
+    /// succeeded (`None` if unknown, such as for detached commands).
+    pub fn add_run(&mut self, id: &CommandId, success: Option<bool>) {
+        self.add_entry(id);
+        self.last_runs.insert(
+            id.clone(),
+            LastRun {
+                timestamp: unix_now(),
+                success,
+            },
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LastRun {
+    /// UNIX time in seconds.
+    pub timestamp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub success: Option<bool>,
+}
+
+impl LastRun {
+    /// Compact summary such as `✓ 2m ago`, relative to `now` in UNIX seconds.
+    pub fn summary(&self, now: u64) -> String {
+        let ago = match now.saturating_sub(self.timestamp) {
+            secs @ 0..60 => format!("{secs}s ago"),
+            secs @ 60..3600 => format!("{}m ago", secs / 60),
+            secs @ 3600..86400 => format!("{}h ago", secs / 3600),
+            secs => format!("{}d ago", secs / 86400),
+        };
+
+        match self.success {
+            Some(true) => format!("✓ {ago}"),
+            Some(false) => format!("✗ {ago}"),
+            None => ago,
+        }
+    }
+}
+
+/// Current UNIX time in seconds.
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct Registry {
+    /// Source files, in loading order. Commands of a source override the ones
+    /// with the same id of previous sources.
+    pub sources: Vec<PathBuf>,
+    /// Hash of each source content when it was last loaded.
+    #[serde(default)]
+    pub source_hashes: BTreeMap<PathBuf, String>,
+    /// Environment variables containing the TOML content of sources.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub env_sources: BTreeSet<String>,
+    pub commands: BTreeMap<CommandId, UserCommand>,
+    /// Commands of source files replaced by the ones of later sources, kept
+    /// to reuse them if their source didn't change but the later one did.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub overridden: Vec<UserCommand>,
+}
+
+impl Registry {
+    pub fn add_source(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        let path = std::fs::canonicalize(&path)
+            .map_err(|e| anyhow!("unable to find source \"{}\": {e}", path.display()))?;
+        println!("Adding source \"{}\"", path.display());
+
+        let mut commands = BTreeMap::new();
+        if load_scripts_for_source(&mut commands, path.clone())? {
+            self.source_hashes.insert(path.clone(), hash_source(&path)?);
+        }
+        merge_commands(&mut self.commands, &mut self.overridden, commands);
+        if !self.sources.contains(&path) {
+            self.sources.push(path);
+        }
+        warn_duplicate_names(&self.commands);
+
+        Ok(())
+    }
+
+    /// Add a source defined by the content of an environment variable.
+    pub fn add_env_source(&mut self, var: String) -> anyhow::Result<()> {
+        println!("Adding source from environment variable {var}");
+
+        let mut commands = BTreeMap::new();
+        load_scripts_for_env_source(&mut commands, &var)?;
+        merge_commands(&mut self.commands, &mut self.overridden, commands);
+        self.env_sources.insert(var);
+        warn_duplicate_names(&self.commands);
+
+        Ok(())
+    }
+
+    /// Remove a source defined by an environment variable. Its commands are
+    /// kept until the next reload.
+    pub fn remove_env_source(&mut self, var: &str) -> anyhow::Result<()> {
+        if !self.env_sources.remove(var) {
+            bail!("Environment variable was not a registered source");
+        }
+
+        println!("Removed source from environment variable {var}");
+
+        Ok(())
+    }
+
+    /// Remove a source. Its commands are kept until the next reload.
+    pub fn remove_source(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        // try to remove raw path, this allow to delete sources that no
+        // longer exist on disk
+        let path = self.registered_source_path(path)?;
+        self.sources.retain(|source| source != &path);
+        self.source_hashes.remove(&path);
+
+        println!("Removed source \"{}\"", path.display());
+
+        Ok(())
+    }
+
+    /// Move a source at `position` (starting from 1) in the loading order,
+    /// then reload all sources as the commands they override may change.
+    pub fn move_source(&mut self, path: PathBuf, position: usize) -> anyhow::Result<()> {
+        let path = self.registered_source_path(path)?;
+        if position == 0 {
+            bail!("Positions start from 1");
+        }
+
+        self.sources.retain(|source| source != &path);
+        let index = (position - 1).min(self.sources.len());
+        println!(
+            "Moving source \"{}\" at position {}",
+            path.display(),
+            index + 1
+        );
+        self.sources.insert(index, path);
+
+        self.reload()
+    }
+
+    /// Path of a registered source as it is stored, trying the raw path first
+    /// to find sources that no longer exist on disk.
+    fn registered_source_path(&self, path: PathBuf) -> anyhow::Result<PathBuf> {
+        if self.sources.contains(&path) {
+            return Ok(path);
+        }
+
+        let path = std::fs::canonicalize(path)?;
+        if !self.sources.contains(&path) {
+            bail!("Path was not a registered source");
+        }
+
+        Ok(path)
+    }
+
+    /// Same as [`Self::reload`] without printing anything, for the menu.
+    pub fn reload_quietly(&mut self) -> anyhow::Result<()> {
+        QUIET.set(true);
+        let result = self.reload();
+        QUIET.set(false);
+        result
+    }
+
+    /// Reload commands from all sources, skipping source files that didn't
+    /// change.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let mut commands = BTreeMap::new();
+        let mut overridden = Vec::new();
+        let mut source_hashes = BTreeMap::new();
+        let mut unchanged = 0;
+        let mut reloaded = 0;
+
+        for path in &self.sources {
+            let hash = hash_source(path)?;
+            let mut source_commands = BTreeMap::new();
+
+            if self.source_hashes.get(path) == Some(&hash) {
+                // Source didn't change since last load, we can reuse the
+                // commands we already have (which keep their last used
+                // arguments), including the ones overridden by other sources.
+                report!("Unchanged source: {}", path.display());
+                source_commands.extend(
+                    self.commands
+                        .values()
+                        .chain(&self.overridden)
+                        .filter(|command| command.source_path.as_ref() == Some(path))
+                        .map(|command| (command.generate_id(), command.clone())),
+                );
+                unchanged += 1;
+            } else {
+                // Skipped sources are not marked as loaded, to load them
+                // after iforgor is updated even if they didn't change.
+                if !load_scripts_for_source(&mut source_commands, path.clone())? {
+                    continue;
+                }
+                reloaded += 1;
+            }
+
+            merge_commands(&mut commands, &mut overridden, source_commands);
+            source_hashes.insert(path.clone(), hash);
+        }
+
+        // Sources from environment variables are always reloaded, as they
+        // are cheap to read.
+        for var in &self.env_sources {
+            let mut source_commands = BTreeMap::new();
+            if load_scripts_for_env_source(&mut source_commands, var)? {
+                reloaded += 1;
+            }
+            merge_commands(&mut commands, &mut overridden, source_commands);
+        }
+
+        report!("{reloaded} source(s) reloaded, {unchanged} unchanged");
+        warn_duplicate_names(&commands);
+
+        self.commands = commands;
+        self.overridden = overridden;
+        self.source_hashes = source_hashes;
+
+        Ok(())
+    }
+
+    /// Delete a command from its source file after asking for confirmation,
+    /// then reload the sources.
+    pub fn delete_command(&mut self, id: &CommandId) -> anyhow::Result<()> {
+        let Some(command) = self.commands.get(id) else {
+            bail!("Unknown command ID {id}")
+        };
+
+        let Some(source_path) = command.source_path.clone() else {
+            bail!("command doesn't have a known source file")
+        };
+
+        if source_path.is_dir() {
+            let file = command.file_path().unwrap_or(source_path);
+            bail!(
+                "command comes from a folder of scripts, delete its file \"{}\" instead",
+                file.display()
+            );
+        }
+
+        let question = format!(
+            "🗑️ Delete \"{}\" from \"{}\"?",
+            command.name,
+            source_path.display()
+        );
+        if !ask_confirmation(&question)? {
+            println!("Deletion aborted");
+            return Ok(());
+        }
+
+        let content = std::fs::read_to_string(&source_path)?;
+        let Some(content) = remove_source_entry(&content, id)? else {
+            bail!(
+                "command not found in \"{}\", it may have been modified since the last reload",
+                source_path.display()
+            );
+        };
+
+        std::fs::write(&source_path, content)?;
+        println!("Command deleted");
+
+        self.reload()
+    }
+
+    pub fn run_script_by_id(
+        &mut self,
+        id: &CommandId,
+        options: &RunOptions,
+    ) -> anyhow::Result<RunOutcome> {
+        let Some(entry) = self.commands.get_mut(id) else {
+            bail!("Unknown command ID {id}")
+        };
+
+        let args_values = entry.prompt_args()?;
+        let outcome = entry.run_with_args(&args_values, options);
+
+        if let Some(audit_log) = &options.audit_log {
+            let status = match &outcome {
+                Ok(outcome) => audit::Status::Ran(outcome.status),
+                Err(e) if e.is::<Aborted>() => audit::Status::Aborted,
+                Err(_) => audit::Status::Error,
+            };
+            if let Err(e) = audit::log(
+                audit_log,
+                id,
+                &entry.name,
+                &entry.args,
+                &args_values,
+                status,
+            ) {
+                println!("⚠ Unable to write in the audit log: {e}");
+            }
+        }
+
+        let outcome = outcome?;
+        entry.remember_args(args_values);
+
+        Ok(outcome)
+    }
+}
+
+impl UserCommand {
+    /// Set used values as new default. Values of a variadic argument are not
+    /// suggested again.
+    fn remember_args(&mut self, mut args_values: Vec<String>) {
+        if self.variadic {
+            args_values.truncate(self.args.len().saturating_sub(1));
+        }
+        self.args_default = args_values;
+    }
+
+    /// Ask the user the values of the command arguments, suggesting the
+    /// default or last used values.
+    pub fn prompt_args(&self) -> anyhow::Result<Vec<String>> {
+        let mut args_values = Vec::new();
+        if !self.args.is_empty() {
+            println!(
+                "Default values or previously used values will be suggested, type nothing \
+and press Enter to use it, or type anything (even a space) to discard it.
+
+This script requires the following arguments (use Ctrl+C to abort execution):\n"
+            );
+        }
+
+        for (i, arg) in self.args.iter().enumerate() {
+            if self.variadic && i + 1 == self.args.len() {
+                args_values.extend(prompt_variadic_arg(arg)?);
+                break;
+            }
+
+            let mut buf = String::new();
+
+            if let Some(def) = self
+                .args_default
+                .get(i)
+                .filter(|def| !def.trim().is_empty())
+            {
+                println!("- {arg} [Default: {def}]:");
+                std::io::stdin().read_line(&mut buf)?;
+                if buf.trim_end_matches(['\n', '\r']).is_empty() {
+                    println!("Using default!");
+                    buf = def.clone();
+                }
+            } else {
+                print!("- {arg}: ");
+                std::io::stdout().flush()?;
+                std::io::stdin().read_line(&mut buf)?;
+            }
+
+            args_values.push(buf.trim().to_string());
+        }
+
+        Ok(args_values)
+    }
+
+    /// Run the command with the provided argument values, asking confirmation
+    /// first if it is risky.
+    pub fn run_with_args(
+        &self,
+        args_values: &[String],
+        options: &RunOptions,
+    ) -> anyhow::Result<RunOutcome> {
+        let outside_home = self.is_outside_home();
+
+        let UserCommand {
+            name,
+            script,
+            shell,
+            risky,
+            stdin,
+            env,
+            env_file,
+            source_path,
+            force_color,
+            before,
+            after,
+            confirm_message,
+            detach,
+            retries,
+            retry_delay_secs,
+            countdown_secs,
+            ..
+        } = self;
+
+        // Inline `env` takes precedence over `env_file`, which both take
+        // precedence over variables set by iforgor.
+        let mut env_vars = BTreeMap::new();
+        env_vars.insert("IFORGOR_CMD_NAME".to_string(), name.clone());
+        env_vars.insert("IFORGOR_CMD_ID".to_string(), self.generate_id());
+        if *force_color {
+            for var in FORCE_COLOR_VARS {
+                env_vars.insert(var.to_string(), "1".to_string());
+            }
+        }
+        if let Some(env_file) = env_file {
+            let env_file = resolve_source_relative_path(env_file, source_path.as_deref());
+            let vars = parse_env_file(&env_file).map_err(|e| {
+                anyhow!(
+                    "unable to load env file \"{}\" of \"{name}\": {e}",
+                    env_file.display()
+                )
+            })?;
+            env_vars.extend(vars);
+        }
+        env_vars.extend(env.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        let trusted = options.skip_all_confirm || options.trusted_ids.contains(&self.trust_id());
+        if !trusted && ((*risky && !options.skip_risky_confirm) || confirm_message.is_some()) {
+            let message = confirm_message
+                .as_deref()
+                .unwrap_or("Script is tagged as risky, are you sure you want to run it?");
+            if !ask_confirmation(&format!("⚠️ {message}"))? {
+                return Err(Aborted("Aborted execution of risky script").into());
+            }
+        }
+
+        if let Some(source_path) = source_path.as_ref().filter(|_| outside_home) {
+            println!(
+                "⚠ This command comes from \"{}\" which is outside of your home directory.",
+                source_path.display()
+            );
+        }
+
+        if let Some(secs) = countdown_secs {
+            if !countdown(Duration::from_secs(*secs))? {
+                return Err(Aborted("Aborted execution during countdown").into());
+            }
+        }
+
+        ctrlc_handler::set_mode(ctrlc_handler::Mode::Ignore);
+
+        if let Some(before) = before {
+            let hook_status = PreparedScript::new(before, args_values, *shell)?
+                .envs(&env_vars)
+                .run()?;
+
+            if !hook_status.success() {
+                ctrlc_handler::set_mode(ctrlc_handler::Mode::Kill);
+                bail!("`before` hook of \"{name}\" failed ({hook_status}), aborting");
+            }
+        }
+
+        let start = Instant::now();
+
+        if *detach {
+            ctrlc_handler::set_mode(ctrlc_handler::Mode::Kill);
+            println!("💭 Starting \"{name}\" with shell \"{shell:?}\" in a new terminal");
+            PreparedScript::new(script, args_values, *shell)?
+                .envs(&env_vars)
+                .spawn_in_terminal()?;
+            return Ok(RunOutcome {
+                status: None,
+                duration: start.elapsed(),
+            });
+        }
+
+        println!("💭 Running \"{name}\" with shell \"{shell:?}\"\n");
+        let mut attempt = 1;
+        let status = loop {
+            let status = PreparedScript::new(script, args_values, *shell)?
+                .keep_temp(options.keep_temp)
+                .envs(&env_vars)
+                .stdin(
+                    stdin
+                        .as_deref()
+                        .map(|stdin| template_args(stdin, args_values)),
+                )
+                .run()?;
+
+            if !should_retry(status, attempt, *retries) {
+                break status;
+            }
+
+            attempt += 1;
+            println!(
+                "\n🔁 Script failed ({status}), attempt {attempt}/{}\n",
+                retries + 1
+            );
+            if let Some(delay) = retry_delay_secs {
+                std::thread::sleep(Duration::from_secs(*delay));
+            }
+        };
+        let duration = start.elapsed();
+
+        // Hook failures are only reported, to not hide the script status.
+        if let Some(after) = after {
+            let code = status
+                .code()
+                .map(|code| code.to_string())
+                .unwrap_or_default();
+            let hook_status = PreparedScript::new(after, std::slice::from_ref(&code), *shell)
+                .and_then(|hook| {
+                    hook.envs(&env_vars)
+                        .envs([("IFORGOR_EXIT_CODE", &code)])
+                        .run()
+                });
+
+            match hook_status {
+                Ok(hook_status) if !hook_status.success() => {
+                    println!("⚠ `after` hook of \"{name}\" failed ({hook_status})")
+                }
+                Ok(_) => (),
+                Err(e) => println!("⚠ Unable to run `after` hook of \"{name}\": {e}"),
+            }
+        }
+        ctrlc_handler::set_mode(ctrlc_handler::Mode::Kill);
+
+        Ok(RunOutcome {
+            status: Some(status),
+            duration,
+        })
+    }
+}
+
+/// Error returned when the user declines to run a command.
+#[derive(Debug)]
+pub struct Aborted(&'static str);
+
+impl std::fmt::Display for Aborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for Aborted {}
+
+/// Result of running a command.
+#[derive(Debug, Clone, Copy)]
+pub struct RunOutcome {
+    /// Exit status of the script, `None` if it has been started in a new
+    /// terminal.
+    pub status: Option<process::ExitStatus>,
+    /// Time spent running the script, without prompts and hooks.
+    pub duration: Duration,
+}
+
+impl RunOutcome {
+    /// Stable `key=value` description of the outcome, used by `--porcelain`.
+    /// The name is quoted if needed, and `exit_code` is empty if the script
+    /// didn't exit by itself.
+    pub fn porcelain(&self, id: &str, name: &str) -> String {
+        let (status, exit_code) = match self.status {
+            None => ("detached", None),
+            Some(status) => match status.code() {
+                Some(code) => ("exited", Some(code)),
+                None => ("signaled", None),
+            },
+        };
+
+        format!(
+            "id={id} name={} status={status} exit_code={} duration_ms={}",
+            snailquote::escape(name),
+            exit_code.map(|code| code.to_string()).unwrap_or_default(),
+            self.duration.as_millis()
+        )
+    }
+}
+
+/// Compares 2 versions in `MAJOR.MINOR.PATCH` format (missing components
+/// are 0, pre-release suffixes are ignored). Returns `None` if any of them
+/// can't be parsed.
+fn is_version_older(version: &str, than: &str) -> Option<bool> {
+    fn parse(version: &str) -> Option<[u64; 3]> {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split(['-', '+']).next()?;
+
+        let mut parts = [0; 3];
+        let mut components = version.split('.');
+        for part in &mut parts {
+            if let Some(component) = components.next() {
+                *part = component.parse().ok()?;
+            }
+        }
+
+        if components.next().is_some() {
+            return None;
+        }
+
+        Some(parts)
+    }
+
+    Some(parse(version)? < parse(than)?)
+}
+
+/// Prompt values of a variadic argument until an empty line is entered.
+fn prompt_variadic_arg(arg: &str) -> anyhow::Result<Vec<String>> {
+    println!("- {arg} (one value per line, empty line to finish):");
+
+    let mut values = Vec::new();
+    loop {
+        print!("  {}. ", values.len() + 1);
+        std::io::stdout().flush()?;
+
+        let mut buf = String::new();
+        std::io::stdin().read_line(&mut buf)?;
+
+        match buf.trim() {
+            "" => return Ok(values),
+            value => values.push(value.to_string()),
+        }
+    }
+}
+
+/// Whether a script should run again after its `attempt`-th run (starting
+/// from 1) exited with `status`. Scripts terminated by a signal (such as
+/// Ctrl+C) are not retried.
+fn should_retry(status: process::ExitStatus, attempt: u32, retries: u32) -> bool {
+    !status.success() && status.code().is_some() && attempt <= retries
+}
+
+/// Replace `{{N}}` placeholders in `text` by the value of the N-th argument
+/// (starting from 1). Values are copied as is, placeholders they contain are
+/// not replaced.
+fn template_args(text: &str, args: &[String]) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest[2..].split_once("}}").and_then(|(number, _)| {
+            if !number.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let index = number.parse::<usize>().ok()?.checked_sub(1)?;
+            Some((args.get(index)?, number.len()))
+        });
+
+        match value {
+            Some((value, len)) => {
+                output.push_str(value);
+                rest = &rest[len + 4..];
+            }
+            // The second brace may start a placeholder.
+            None => {
+                output.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+/// Resolve a path written in a source file: `~` is replaced by the home
+/// directory, and relative paths are relative to the source file folder.
+fn resolve_source_relative_path(path: &Path, source_path: Option<&Path>) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~") {
+        if let Some(home) = home_dir() {
+            return home.join(rest);
+        }
+    }
+
+    match source_path.and_then(|source| source.parent()) {
+        Some(folder) if path.is_relative() => folder.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Parse a dotenv file made of `KEY=VALUE` lines. Empty lines and lines
+/// starting with `#` are ignored, and values can be quoted.
+fn parse_env_file(path: &Path) -> anyhow::Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path)?;
+    let mut vars = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("line {} is not in format KEY=VALUE", i + 1);
+        };
+
+        let value = value.trim();
+        let value = ['"', '\'']
+            .into_iter()
+            .find_map(|quote| {
+                value
+                    .strip_prefix(quote)
+                    .and_then(|value| value.strip_suffix(quote))
+            })
+            .unwrap_or(value);
+
+        vars.push((key.trim().to_string(), value.to_string()));
+    }
+
+    Ok(vars)
+}
+
+/// Key (with Ctrl) to edit the source of a command from the menu.
+const EDIT_ACTION_KEY: char = 'e';
+/// Key (with Ctrl) to delete a command from its source from the menu.
+const DELETE_ACTION_KEY: char = 'd';
+/// Key (with Ctrl) to read the script of a command from the menu.
+const VIEW_ACTION_KEY: char = 'v';
+
+/// Environment variables commonly used by tools to force colored output even
+/// if they don't output into a terminal.
+const FORCE_COLOR_VARS: [&str; 2] = ["CLICOLOR_FORCE", "FORCE_COLOR"];
+
+/// Options affecting how commands are run.
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Don't delete the temporary script files after execution.
+    pub keep_temp: bool,
+    /// File in which executions of registered commands are logged.
+    pub audit_log: Option<PathBuf>,
+    /// Run risky commands without asking confirmation.
+    pub skip_risky_confirm: bool,
+    /// Run all commands without asking confirmation, even the ones with a
+    /// `confirm_message`.
+    pub skip_all_confirm: bool,
+    /// Commands run without asking any confirmation.
+    pub trusted_ids: BTreeSet<CommandId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CommandsSource {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Oldest version of iforgor able to use this source. Commands are
+    /// skipped with a warning by older versions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_version: Option<String>,
+
+    /// Fields applied to entries which don't set them, such as `shell` or
+    /// `tags`. Use [`CommandsSource::read`] to get entries with them applied.
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    pub defaults: toml::Table,
+
+    #[serde(default)]
+    pub entries: Vec<UserCommand>,
+}
+
+impl CommandsSource {
+    /// Read a source file, with its `defaults` applied to its entries, or a
+    /// folder of scripts (see [`CommandsSource::from_dir`]).
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        if path.is_dir() {
+            return Self::from_dir(path);
+        }
+
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Make a source from a folder of scripts, each file being a command named
+    /// after it (without its extension). Files ending with `.ps1`, `.bat` or
+    /// `.cmd` are run with PowerShell or cmd, files with a shebang or ending
+    /// with `.sh` with `sh` (which honors the shebang), and other files with
+    /// the default shell. Files which are not text are ignored.
+    pub fn from_dir(path: &Path) -> anyhow::Result<Self> {
+        let mut entries = Vec::new();
+
+        for file in script_files(path)? {
+            let script = match std::fs::read_to_string(&file) {
+                Ok(script) => script,
+                Err(e) if e.kind() == std::io::ErrorKind::InvalidData => continue,
+                Err(e) => bail!("unable to read \"{}\": {e}", file.display()),
+            };
+
+            let extension = file.extension().and_then(|ext| ext.to_str());
+            let shell = match extension {
+                Some("ps1") => Shell::Powershell,
+                Some("bat" | "cmd") => Shell::Cmd,
+                Some("sh") => Shell::Sh,
+                _ if script.starts_with("#!") => Shell::Sh,
+                _ => Shell::default(),
+            };
+
+            let name = file
+                .file_stem()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            entries.push(UserCommand {
+                name,
+                script,
+                shell,
+                ..Default::default()
+            });
+        }
+
+        Ok(Self {
+            entries,
+            ..Default::default()
+        })
+    }
+
+    /// Parse a source, with its `defaults` and the matching `overrides` of
+    /// its entries applied. They are applied before deserializing entries, as
+    /// entries can't tell which fields have been omitted once deserialized.
+    pub fn from_toml(content: &str) -> anyhow::Result<Self> {
+        let mut source: toml::Table = toml::from_str(content)?;
+
+        let defaults = match source.get("defaults") {
+            Some(toml::Value::Table(defaults)) => defaults.clone(),
+            _ => toml::Table::new(),
+        };
+
+        let entries = source
+            .get_mut("entries")
+            .and_then(|entries| entries.as_array_mut())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.as_table_mut());
+
+        for entry in entries {
+            normalize_entry(entry, &defaults)?;
+        }
+
+        Ok(source.try_into()?)
+    }
+}
+
+/// Apply the source `defaults`, the matching `overrides` and `script_lines`
+/// to a raw entry, which can then be deserialized.
+fn normalize_entry(entry: &mut toml::Table, defaults: &toml::Table) -> anyhow::Result<()> {
+    for (key, value) in defaults {
+        entry.entry(key).or_insert_with(|| value.clone());
+    }
+
+    if let Some(overrides) = entry.remove("overrides") {
+        let toml::Value::Table(overrides) = overrides else {
+            bail!("`overrides` must be a table");
+        };
+
+        // Overrides are applied in the order of their conditions.
+        for (condition, fields) in overrides {
+            let toml::Value::Table(fields) = fields else {
+                bail!("override \"{condition}\" must be a table");
+            };
+
+            if override_matches(&condition)? {
+                entry.extend(fields);
+            }
+        }
+    }
+
+    if let Some(lines) = entry.remove("script_lines") {
+        if entry.contains_key("script") {
+            bail!("`script` and `script_lines` can't be both set");
+        }
+
+        let lines: Vec<String> = lines
+            .try_into()
+            .map_err(|_| anyhow!("`script_lines` must be an array of strings"))?;
+        entry.insert("script".to_string(), lines.join("\n").into());
     }
+
+    Ok(())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct CommandsSource {
-    pub entries: Vec<UserCommand>,
+/// Remove the entry of command `id` from the content of a source, keeping
+/// the other entries as written (with their comments, `overrides`, etc).
+/// Returns `None` if no entry has this id.
+fn remove_source_entry(content: &str, id: &CommandId) -> anyhow::Result<Option<String>> {
+    let source: toml::Table = toml::from_str(content)?;
+    let defaults = match source.get("defaults") {
+        Some(toml::Value::Table(defaults)) => defaults.clone(),
+        _ => toml::Table::new(),
+    };
+
+    // Entries are compared once normalized, as the id is computed from the
+    // loaded command.
+    let raw_entries = source
+        .get("entries")
+        .and_then(|entries| entries.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let index = raw_entries.iter().position(|entry| {
+        let Some(mut entry) = entry.as_table().cloned() else {
+            return false;
+        };
+
+        normalize_entry(&mut entry, &defaults).is_ok()
+            && toml::Value::Table(entry)
+                .try_into::<UserCommand>()
+                .is_ok_and(|command| &command.generate_id() == id)
+    });
+    let Some(index) = index else {
+        return Ok(None);
+    };
+
+    let mut document: toml_edit::DocumentMut = content.parse()?;
+    match document.get_mut("entries") {
+        Some(toml_edit::Item::ArrayOfTables(entries)) => entries.remove(index),
+        Some(toml_edit::Item::Value(toml_edit::Value::Array(entries))) => {
+            entries.remove(index);
+        }
+        _ => bail!("unexpected format of `entries`"),
+    }
+
+    Ok(Some(document.to_string()))
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -379,10 +2537,18 @@ pub enum SystemCommand {
     RemoveSource,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct UserCommand {
     pub name: String,
 
+    /// Short description, which is searched along with the name.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Tags used to search commands with `#tag`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
     pub script: String,
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -391,20 +2557,96 @@ pub struct UserCommand {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub args_default: Vec<String>,
 
+    /// The last argument takes any number of values, which are prompted
+    /// until an empty line is entered.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub variadic: bool,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub only_on: Option<Platform>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Shell::is_default")]
     pub shell: Shell,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub only_in_dir: Option<String>,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub risky: bool,
+
+    /// Confirmation question asked before running the command, replacing the
+    /// generic one of risky commands.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confirm_message: Option<String>,
+
+    /// Seconds counted down before running the command, during which Esc
+    /// aborts the execution.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub countdown_secs: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_if: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stdin: Option<String>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_file: Option<PathBuf>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub force_color: bool,
+
+    /// Start the script in a new terminal window instead of running it
+    /// inline.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub detach: bool,
+
+    /// Never add the command to the history, for utilities which would
+    /// clutter it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_history: bool,
+
+    /// Number of times the script is run again if it fails.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub retries: u32,
+
+    /// Seconds waited before running the script again when it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_delay_secs: Option<u64>,
+
+    /// Snippet run before the script (with the same shell and arguments),
+    /// which aborts the execution if it fails.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+
+    /// Snippet run after the script (with the same shell), which receives
+    /// its exit code as first argument and in `IFORGOR_EXIT_CODE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+
+    /// Digit running the command when pressed in the menu while the search
+    /// is empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<char>,
+
+    /// Commands with a higher priority are displayed first when searching.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+
+    /// Source file the command was loaded from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_path: Option<PathBuf>,
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum,
+)]
 pub enum Shell {
     #[serde(alias = "sh")]
     Sh,
@@ -414,6 +2656,25 @@ pub enum Shell {
     Powershell,
 }
 
+fn is_zero(value: &u32) -> bool {
+    *value == 0
+}
+
+impl Shell {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Program used to run scripts with this shell.
+    pub fn interpreter(&self) -> &'static str {
+        match self {
+            Self::Sh => "sh",
+            Self::Cmd => "cmd",
+            Self::Powershell => "PowerShell",
+        }
+    }
+}
+
 impl Default for Shell {
     #[cfg(target_os = "linux")]
     fn default() -> Self {
@@ -441,147 +2702,1087 @@ impl UserCommand {
         let hash = hasher.finalize();
         base16ct::lower::encode_string(&hash)
     }
-}
 
-pub fn execute_script(
-    script: &str,
-    args: &[String],
-    shell: Shell,
-) -> anyhow::Result<process::ExitStatus> {
-    match shell {
-        Shell::Sh => execute_script_sh(script, args),
-        Shell::Cmd => execute_script_cmd(script, args),
-        Shell::Powershell => execute_script_powershell(script, args),
+    /// Hash of everything affecting what the command executes, including the
+    /// content of its `env_file`, used to trust it until any of it is
+    /// modified. Fields only used to display or filter commands are ignored,
+    /// as well as the default argument values which are replaced by the last
+    /// used ones (and displayed when prompted).
+    pub fn trust_id(&self) -> String {
+        let executed = UserCommand {
+            name: String::new(),
+            description: None,
+            tags: Vec::new(),
+            args_default: Vec::new(),
+            only_on: None,
+            only_in_dir: None,
+            requires: Vec::new(),
+            show_if: None,
+            no_history: false,
+            hotkey: None,
+            priority: None,
+            ..self.clone()
+        };
+        let serialized = toml::to_string(&executed).expect("command to serialize");
+
+        let mut hasher = Sha3_256::new();
+        hasher.update(serialized.as_bytes());
+
+        // A missing env file fails the execution, it doesn't need to be
+        // told apart from an empty one.
+        if let Some(env_file) = &self.env_file {
+            let env_file = resolve_source_relative_path(env_file, self.source_path.as_deref());
+            hasher.update([0]);
+            hasher.update(std::fs::read(env_file).unwrap_or_default());
+        }
+
+        let hash = hasher.finalize();
+        base16ct::lower::encode_string(&hash)
     }
-}
 
-pub fn execute_script_sh(script: &str, args: &[String]) -> anyhow::Result<process::ExitStatus> {
-    // Create a temporary folder in which the script file will be
-    // created.
-    let tmp_dir = tempfile::tempdir()?;
-    let file_path = tmp_dir.path().join("script");
+    /// Fields which are set but not used as the command is `detach`ed.
+    pub fn ignored_when_detached(&self) -> Vec<&'static str> {
+        if !self.detach {
+            return Vec::new();
+        }
+
+        [
+            ("retries", self.retries > 0),
+            ("retry_delay_secs", self.retry_delay_secs.is_some()),
+            ("after", self.after.is_some()),
+            ("stdin", self.stdin.is_some()),
+        ]
+        .into_iter()
+        .filter(|(_, set)| *set)
+        .map(|(field, _)| field)
+        .collect()
+    }
 
-    // Create the file, write into it and change its permissions (on Linux).
-    // File is closed at the end of scope, which will allow to
-    // execute it after.
-    {
-        let mut tmp_file = File::create(&file_path)?;
-        tmp_file.write_all(b"#!/bin/sh\n")?;
-        tmp_file.write_all(script.as_bytes())?;
-        tmp_file.flush()?;
+    pub fn is_compatible_with_current_platform(&self) -> bool {
+        match self.only_on {
+            Some(Platform::Windows) => cfg!(target_os = "windows"),
+            Some(Platform::Linux) => cfg!(target_os = "linux"),
+            None => true,
+        }
+    }
 
-        #[cfg(target_os = "linux")]
-        {
-            // Set permissions to read/execute.
-            use std::os::unix::fs::PermissionsExt;
-            let mut permissions = tmp_file.metadata()?.permissions();
-            permissions.set_mode(0o500);
-            tmp_file.set_permissions(permissions)?;
+    /// File defining the command: its source file, or its script file if it
+    /// comes from a folder of scripts (or the folder if the file can't be
+    /// found anymore).
+    pub fn file_path(&self) -> Option<PathBuf> {
+        let source_path = self.source_path.as_ref()?;
+        if !source_path.is_dir() {
+            return Some(source_path.clone());
         }
+
+        let script_file = script_files(source_path)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|file| std::fs::read_to_string(file).is_ok_and(|script| script == self.script));
+        Some(script_file.unwrap_or_else(|| source_path.clone()))
     }
 
-    // Execute the script
-    let mut child = process::Command::new(file_path)
-        .args(args)
-        .spawn()
-        .expect("script command failed to start");
+    /// Is the command loaded from a source outside of the home directory,
+    /// which could be surprising (`/tmp`, removable drive, etc).
+    pub fn is_outside_home(&self) -> bool {
+        let (Some(source_path), Some(home)) = (&self.source_path, home_dir()) else {
+            return false;
+        };
 
-    let status = child.wait()?;
+        !source_path.starts_with(home)
+    }
+
+    /// Entry displayed in the interactive menu.
+    pub fn list_entry(&self, id: &CommandId) -> ichoose::ListEntry<CommandId> {
+        ichoose::ListEntry {
+            badges: self.badges(),
+            tags: self.tags.clone(),
+            description: self.description.clone(),
+            details: Some(self.script.clone()),
+            origin: self
+                .source_path
+                .as_ref()
+                .and_then(|path| path.file_name())
+                .map(|name| name.to_string_lossy().into_owned()),
+            hotkey: self.hotkey,
+            priority: self.priority.unwrap_or_default(),
+            ..ichoose::ListEntry::new(id.clone(), self.display_name())
+        }
+    }
+
+    /// Badges showing the platform restriction and the shell if it isn't the
+    /// default one.
+    pub fn badges(&self) -> Vec<String> {
+        let mut badges = Vec::new();
 
-    tmp_dir.close()?;
+        match self.only_on {
+            Some(Platform::Linux) => badges.push("linux".to_string()),
+            Some(Platform::Windows) => badges.push("win".to_string()),
+            None => (),
+        }
+
+        if self.shell != Shell::default() {
+            let badge = match self.shell {
+                Shell::Sh => "sh",
+                Shell::Cmd => "cmd",
+                Shell::Powershell => "ps",
+            };
+            badges.push(badge.to_string());
+        }
+
+        badges
+    }
 
-    Ok(status)
+    /// Name displayed in the list, with a warning sign if the command is
+    /// outside of the home directory.
+    pub fn display_name(&self) -> String {
+        if self.is_outside_home() {
+            format!("⚠ {}", self.name)
+        } else {
+            self.name.clone()
+        }
+    }
 }
 
-pub fn execute_script_cmd(script: &str, args: &[String]) -> anyhow::Result<process::ExitStatus> {
-    // Create a temporary folder in which the script file will be
-    // created.
-    let tmp_dir = tempfile::tempdir()?;
-    let file_path = tmp_dir.path().join("script.bat");
+/// Check if the `only_in_dir` pattern of the command matches the current
+/// folder, or any of its parents if `subdirs` is true.
+fn filter_only_in_dir(current_dir: &Path, command: &UserCommand, subdirs: bool) -> bool {
+    let Some(only_in_dir) = &command.only_in_dir else {
+        return true;
+    };
+
+    // Invalid patterns are reported by `warn_invalid_only_in_dir`.
+    let Ok(pattern) = glob::Pattern::new(only_in_dir) else {
+        return false;
+    };
 
-    // Create the file and write into it.
-    // File is closed at the end of scope, which will allow to
-    // execute it after.
-    {
-        let mut tmp_file = File::create(&file_path)?;
-        tmp_file.write_all(b"@echo off\n")?;
-        tmp_file.write_all(script.as_bytes())?;
-        tmp_file.flush()?;
+    if subdirs {
+        current_dir.ancestors().any(|dir| pattern.matches_path(dir))
+    } else {
+        pattern.matches_path(current_dir)
     }
+}
 
-    // Execute the script
-    let mut child = process::Command::new(file_path)
-        .args(args)
-        .spawn()
-        .expect("script command failed to start");
+/// Warn about commands with an invalid `only_in_dir` pattern, which are
+/// never displayed.
+fn warn_invalid_only_in_dir(registry: &Registry) {
+    for command in registry.commands.values() {
+        let Some(only_in_dir) = &command.only_in_dir else {
+            continue;
+        };
 
-    let status = child.wait()?;
+        if let Err(e) = glob::Pattern::new(only_in_dir) {
+            eprintln!(
+                "⚠ \"{}\" is hidden as its `only_in_dir` pattern is invalid: {e}",
+                command.name
+            );
+        }
+    }
+}
+
+fn filter_requires(command: &UserCommand) -> bool {
+    command
+        .requires
+        .iter()
+        .all(|executable| find_executable(executable).is_some())
+}
+
+/// Search for an executable in the `PATH`, like `which` would do.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let name = Path::new(name);
+
+    // A path (relative or absolute) is checked as is.
+    if name.components().count() > 1 {
+        return is_executable(name).then(|| name.to_path_buf());
+    }
 
-    tmp_dir.close()?;
+    let paths = std::env::var_os("PATH")?;
+
+    // On Windows executables are found without their extension.
+    let extensions: Vec<String> = if cfg!(target_os = "windows") {
+        std::env::var("PATHEXT")
+            .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+            .split(';')
+            .map(|ext| ext.to_string())
+            .chain([String::new()])
+            .collect()
+    } else {
+        vec![String::new()]
+    };
 
-    Ok(status)
+    std::env::split_paths(&paths).find_map(|dir| {
+        extensions.iter().find_map(|ext| {
+            let mut file_name = name.as_os_str().to_owned();
+            file_name.push(ext);
+            let candidate = dir.join(file_name);
+            is_executable(&candidate).then_some(candidate)
+        })
+    })
 }
 
-pub fn execute_script_powershell(
-    script: &str,
-    args: &[String],
-) -> anyhow::Result<process::ExitStatus> {
-    // Create a temporary folder in which the script file will be
-    // created.
-    let tmp_dir = tempfile::tempdir()?;
-    let file_path = tmp_dir.path().join("script.ps1");
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+
+    if !metadata.is_file() {
+        return false;
+    }
 
-    // Create the file and write into it.
-    // File is closed at the end of scope, which will allow to
-    // execute it after.
+    #[cfg(target_os = "linux")]
     {
-        let mut tmp_file = File::create(&file_path)?;
-        tmp_file.write_all(script.as_bytes())?;
-        tmp_file.flush()?;
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return false;
+        }
     }
 
-    let powershell_cmd: Vec<_> = ["&".to_string(), format!("{}", file_path.display())]
-        .into_iter()
-        .chain(args.iter().map(|arg| snailquote::escape(arg).into_owned()))
-        .collect();
+    true
+}
 
-    let powershell_cmd = powershell_cmd.join(" ");
+/// How long the result of a `show_if` predicate is reused before running it
+/// again.
+const SHOW_IF_CACHE_DURATION: Duration = Duration::from_secs(10);
 
-    let args = [
-        "-NoProfile",
-        "-ExecutionPolicy",
-        "Bypass",
-        "-Command",
-        &powershell_cmd,
-    ];
+/// Results of `show_if` predicates, which are cached for a short time to not
+/// run them again each time the list is built.
+#[derive(Default)]
+struct ShowIfCache {
+    results: BTreeMap<(PathBuf, Shell, String), (Instant, bool)>,
+}
 
-    println!("{args:?}");
+impl ShowIfCache {
+    fn check(&mut self, current_dir: &Path, command: &UserCommand) -> bool {
+        let Some(show_if) = &command.show_if else {
+            return true;
+        };
 
-    // Execute the script
-    let mut child = process::Command::new("PowerShell")
-        .args(args)
-        .spawn()
-        .expect("script command failed to start");
+        let key = (current_dir.to_path_buf(), command.shell, show_if.clone());
 
-    let status = child.wait()?;
+        if let Some((time, result)) = self.results.get(&key) {
+            if time.elapsed() < SHOW_IF_CACHE_DURATION {
+                return *result;
+            }
+        }
 
-    tmp_dir.close()?;
+        let result = run_predicate(show_if, command.shell, current_dir);
+        self.results.insert(key, (Instant::now(), result));
+        result
+    }
+}
 
-    Ok(status)
+/// Directory in which commands are displayed: `dir` if provided with
+/// `--filter-dir`, the current one otherwise.
+fn filter_dir(dir: Option<&Path>) -> anyhow::Result<PathBuf> {
+    match dir {
+        Some(dir) => std::fs::canonicalize(dir)
+            .map_err(|e| anyhow!("invalid filter directory \"{}\": {e}", dir.display())),
+        None => Ok(std::env::current_dir()?),
+    }
 }
 
-fn filter_only_in_dir(current_dir: &Path, command: &UserCommand) -> bool {
-    let Some(only_in_dir) = &command.only_in_dir else {
-        return true;
+/// Run a snippet with the provided shell in `dir`, with its output discarded.
+/// Returns `true` if it exited successfully.
+fn run_predicate(snippet: &str, shell: Shell, dir: &Path) -> bool {
+    let mut command = match shell {
+        Shell::Sh => process::Command::new("sh").tap_mut(|c| {
+            c.arg("-c").arg(snippet);
+        }),
+        Shell::Cmd => process::Command::new("cmd").tap_mut(|c| {
+            c.arg("/C").arg(snippet);
+        }),
+        Shell::Powershell => process::Command::new("PowerShell").tap_mut(|c| {
+            c.args(["-NoProfile", "-Command", snippet]);
+        }),
     };
 
-    let pattern = match glob::Pattern::new(only_in_dir) {
-        Ok(p) => p,
-        Err(e) => panic!(
-            "Entry named \"{} has an error in its `only_in_dir` field: {}\"",
-            command.name, e
-        ),
-    };
+    command
+        .current_dir(dir)
+        .env(RUNNING_ENV_VAR, "1")
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_source(content: &str) -> CommandsSource {
+        toml::from_str(content).expect("source to parse")
+    }
+
+    fn parse_single(content: &str) -> UserCommand {
+        let mut source = parse_source(content);
+        assert_eq!(source.entries.len(), 1);
+        source.entries.remove(0)
+    }
+
+    #[test]
+    fn trust_id_covers_executed_fields() {
+        let command = parse_single(
+            r#"
+            [[entries]]
+            name = "Deploy"
+            script = "deploy"
+            "#,
+        );
+        let trust_id = command.trust_id();
+
+        let renamed = UserCommand {
+            name: "Deploy prod".to_string(),
+            args_default: vec!["last".to_string()],
+            ..command.clone()
+        };
+        assert_eq!(renamed.trust_id(), trust_id);
+
+        let with_before = UserCommand {
+            before: Some("rm -rf ~".to_string()),
+            ..command.clone()
+        };
+        assert_ne!(with_before.trust_id(), trust_id);
+
+        let with_env = UserCommand {
+            env: BTreeMap::from([("TARGET".to_string(), "prod".to_string())]),
+            ..command.clone()
+        };
+        assert_ne!(with_env.trust_id(), trust_id);
+        assert_eq!(with_env.generate_id(), command.generate_id());
+
+        let dir = tempfile::tempdir().unwrap();
+        let env_file = dir.path().join(".env");
+        std::fs::write(&env_file, "TARGET=staging").unwrap();
+        let with_env_file = UserCommand {
+            env_file: Some(env_file.clone()),
+            ..command.clone()
+        };
+        let env_file_trust_id = with_env_file.trust_id();
+        std::fs::write(&env_file, "TARGET=prod").unwrap();
+        assert_ne!(with_env_file.trust_id(), env_file_trust_id);
+    }
+
+    #[test]
+    fn detached_commands_ignore_fields() {
+        let mut command = parse_single(
+            r#"
+            [[entries]]
+            name = "Serve"
+            script = "serve"
+            stdin = "input"
+            retries = 2
+            "#,
+        );
+        assert!(command.ignored_when_detached().is_empty());
+
+        command.detach = true;
+        assert_eq!(command.ignored_when_detached(), ["retries", "stdin"]);
+    }
+
+    #[test]
+    fn minimal_entry_uses_defaults() {
+        let command = parse_single(
+            r#"
+            [[entries]]
+            name = "Hello"
+            script = "echo Hello"
+            "#,
+        );
+
+        assert_eq!(command.name, "Hello");
+        assert_eq!(command.script, "echo Hello");
+        assert!(command.args.is_empty());
+        assert!(command.args_default.is_empty());
+        assert!(command.only_on.is_none());
+        assert_eq!(command.shell, Shell::default());
+        assert!(command.only_in_dir.is_none());
+        assert!(!command.risky);
+        assert!(command.requires.is_empty());
+        assert!(command.show_if.is_none());
+        assert!(command.hotkey.is_none());
+        assert!(command.priority.is_none());
+    }
+
+    #[test]
+    fn entry_with_all_fields() {
+        let command = parse_single(
+            r#"
+            [[entries]]
+            name = "Echo"
+            script = """
+            echo $1 $2
+            """
+            args = ["First", "Second"]
+            args_default = ["Hello"]
+            only_on = "Linux"
+            shell = "Sh"
+            only_in_dir = "**/iforgor"
+            risky = true
+            requires = ["echo"]
+            show_if = "true"
+            hotkey = "1"
+            priority = -2
+            variadic = true
+            retries = 3
+            retry_delay_secs = 5
+            countdown_secs = 3
+            no_history = true
+            "#,
+        );
+
+        assert_eq!(command.script, "            echo $1 $2\n            ");
+        assert_eq!(command.args, ["First", "Second"]);
+        assert_eq!(command.args_default, ["Hello"]);
+        assert!(matches!(command.only_on, Some(Platform::Linux)));
+        assert_eq!(command.shell, Shell::Sh);
+        assert_eq!(command.only_in_dir.as_deref(), Some("**/iforgor"));
+        assert!(command.risky);
+        assert_eq!(command.requires, ["echo"]);
+        assert_eq!(command.show_if.as_deref(), Some("true"));
+        assert_eq!(command.hotkey, Some('1'));
+        assert_eq!(command.priority, Some(-2));
+        assert!(command.variadic);
+        assert_eq!(command.retries, 3);
+        assert_eq!(command.retry_delay_secs, Some(5));
+        assert_eq!(command.countdown_secs, Some(3));
+        assert!(command.no_history);
+    }
+
+    #[test]
+    fn shell_aliases() {
+        for (value, expected) in [
+            ("Sh", Shell::Sh),
+            ("sh", Shell::Sh),
+            ("Cmd", Shell::Cmd),
+            ("cmd", Shell::Cmd),
+            ("Powershell", Shell::Powershell),
+            ("powershell", Shell::Powershell),
+        ] {
+            let command = parse_single(&format!(
+                "[[entries]]\nname = \"A\"\nscript = \"a\"\nshell = \"{value}\""
+            ));
+            assert_eq!(command.shell, expected, "for shell = \"{value}\"");
+        }
+
+        let res: Result<CommandsSource, _> =
+            toml::from_str("[[entries]]\nname = \"A\"\nscript = \"a\"\nshell = \"bash\"");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn platform_aliases() {
+        for value in ["Linux", "linux"] {
+            let command = parse_single(&format!(
+                "[[entries]]\nname = \"A\"\nscript = \"a\"\nonly_on = \"{value}\""
+            ));
+            assert!(matches!(command.only_on, Some(Platform::Linux)));
+        }
+
+        for value in ["Windows", "windows"] {
+            let command = parse_single(&format!(
+                "[[entries]]\nname = \"A\"\nscript = \"a\"\nonly_on = \"{value}\""
+            ));
+            assert!(matches!(command.only_on, Some(Platform::Windows)));
+        }
+    }
+
+    #[test]
+    fn missing_required_fields_fail() {
+        let res: Result<CommandsSource, _> = toml::from_str("[[entries]]\nname = \"A\"");
+        assert!(res.is_err());
+
+        let res: Result<CommandsSource, _> = toml::from_str("[[entries]]\nscript = \"a\"");
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn template_args_replaces_placeholders() {
+        let args = ["a".to_string(), "b".to_string()];
+
+        assert_eq!(
+            template_args("{{2}} {{1}} {{3}} {1}", &args),
+            "b a {{3}} {1}"
+        );
+        assert_eq!(
+            template_args("{{{1}}}} {{0}} {{+1}} {{", &args),
+            "{a}} {{0}} {{+1}} {{"
+        );
+    }
+
+    #[test]
+    fn template_args_copies_values_as_is() {
+        let args = ["{{2}}".to_string(), "b".to_string()];
+
+        assert_eq!(template_args("{{1}} {{2}}", &args), "{{2}} b");
+    }
+
+    #[test]
+    fn compares_versions() {
+        assert_eq!(is_version_older("0.2.0", "0.3.0"), Some(true));
+        assert_eq!(is_version_older("0.3.0", "0.3"), Some(false));
+        assert_eq!(is_version_older("1.0.0", "0.10.2"), Some(false));
+        assert_eq!(is_version_older("0.3.0-beta", "v0.3.1"), Some(true));
+        assert_eq!(is_version_older("0.3.0", "latest"), None);
+        assert_eq!(is_version_older("0.3.0", "0.3.0.1"), None);
+    }
+
+    #[test]
+    fn parse_env_file_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            "# comment\n\nA=1\nexport B = two words\nC=\"quoted\"\nD='single'\nE=a=b\n",
+        )
+        .unwrap();
+
+        let vars = parse_env_file(&path).unwrap();
+        let expected = [
+            ("A", "1"),
+            ("B", "two words"),
+            ("C", "quoted"),
+            ("D", "single"),
+            ("E", "a=b"),
+        ]
+        .map(|(k, v)| (k.to_string(), v.to_string()));
+        assert_eq!(vars, expected);
+
+        std::fs::write(&path, "A=1\nINVALID\n").unwrap();
+        assert!(parse_env_file(&path).is_err());
+        assert!(parse_env_file(&dir.path().join("missing.env")).is_err());
+    }
+
+    #[test]
+    fn source_from_folder_of_scripts() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| std::fs::write(dir.path().join(name), content);
+        write("deploy.sh", b"echo deploy").unwrap();
+        write("backup", b"#!/bin/bash\necho backup").unwrap();
+        write("clean.ps1", b"Remove-Item tmp").unwrap();
+        write("build.cmd", b"echo build").unwrap();
+        write(".hidden", b"echo hidden").unwrap();
+        write("binary", &[0xff, 0xfe, 0x00]).unwrap();
+        std::fs::create_dir(dir.path().join("subfolder")).unwrap();
+
+        let source = CommandsSource::read(dir.path()).unwrap();
+        let commands: Vec<_> = source
+            .entries
+            .iter()
+            .map(|command| (command.name.as_str(), command.shell))
+            .collect();
+        assert_eq!(
+            commands,
+            [
+                ("backup", Shell::Sh),
+                ("build", Shell::Cmd),
+                ("clean", Shell::Powershell),
+                ("deploy", Shell::Sh),
+            ]
+        );
+        assert_eq!(source.entries[3].script, "echo deploy");
+
+        let command = UserCommand {
+            source_path: Some(dir.path().to_path_buf()),
+            ..source.entries[3].clone()
+        };
+        assert_eq!(command.file_path(), Some(dir.path().join("deploy.sh")));
+
+        // Renaming a script changes the hash of the folder.
+        let hash = hash_source(dir.path()).unwrap();
+        std::fs::rename(dir.path().join("deploy.sh"), dir.path().join("ship.sh")).unwrap();
+        assert_ne!(hash_source(dir.path()).unwrap(), hash);
+    }
+
+    #[test]
+    fn later_sources_override_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let team = dir.path().join("team.toml");
+        let personal = dir.path().join("personal.toml");
+        std::fs::write(&team, "[[entries]]\nname = \"Team\"\nscript = \"deploy\"").unwrap();
+        std::fs::write(
+            &personal,
+            "[[entries]]\nname = \"Mine\"\nscript = \"deploy\"",
+        )
+        .unwrap();
+
+        let name = |registry: &Registry| {
+            let names: Vec<_> = registry.commands.values().map(|c| c.name.clone()).collect();
+            names.join(",")
+        };
+
+        let mut registry = Registry::default();
+        registry.add_source(team.clone()).unwrap();
+        registry.add_source(personal.clone()).unwrap();
+        assert_eq!(name(&registry), "Mine");
+
+        registry.move_source(personal.clone(), 1).unwrap();
+        assert_eq!(registry.sources[1], std::fs::canonicalize(&team).unwrap());
+        assert_eq!(name(&registry), "Team");
+
+        assert!(registry.move_source(personal, 0).is_err());
+    }
+
+    #[test]
+    fn overridden_commands_are_restored_on_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let team = dir.path().join("team.toml");
+        let personal = dir.path().join("personal.toml");
+        std::fs::write(&team, "[[entries]]\nname = \"Team\"\nscript = \"deploy\"").unwrap();
+        std::fs::write(
+            &personal,
+            "[[entries]]\nname = \"Mine\"\nscript = \"deploy\"",
+        )
+        .unwrap();
+
+        let mut registry = Registry::default();
+        registry.add_source(team.clone()).unwrap();
+        registry.add_source(personal.clone()).unwrap();
+        registry.reload().unwrap();
+
+        // Only the later source changes, the earlier one is reused as is.
+        std::fs::write(&personal, "entries = []").unwrap();
+        registry.reload().unwrap();
+
+        let names: Vec<_> = registry
+            .commands
+            .values()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["Team"]);
+        assert!(registry.overridden.is_empty());
+    }
+
+    #[test]
+    fn entry_overrides() {
+        std::env::set_var("IFORGOR_TEST_SITE", "office");
+
+        let source = CommandsSource::from_toml(
+            r#"
+            [defaults]
+            args = ["registry"]
+
+            [[entries]]
+            name = "Deploy"
+            script = "docker push $1"
+            args_default = ["registry.home"]
+
+            [entries.overrides."env:IFORGOR_TEST_SITE=office"]
+            args_default = ["registry.office"]
+
+            [entries.overrides."env:IFORGOR_TEST_SITE=home"]
+            name = "Deploy at home"
+
+            [entries.overrides."host:not-this-host.invalid"]
+            script = "exit 1"
+            "#,
+        )
+        .unwrap();
+
+        let command = &source.entries[0];
+        assert_eq!(command.name, "Deploy");
+        assert_eq!(command.script, "docker push $1");
+        assert_eq!(command.args, ["registry"]);
+        assert_eq!(command.args_default, ["registry.office"]);
+
+        let res = CommandsSource::from_toml(
+            "[[entries]]\nname = \"A\"\nscript = \"a\"\n[entries.overrides.laptop]\nname = \"B\"",
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn script_lines_are_joined() {
+        let source = CommandsSource::from_toml(
+            r#"
+            [[entries]]
+            name = "Build"
+            script_lines = ["cd $1", "cargo build"]
+            "#,
+        )
+        .unwrap();
+        let lines = &source.entries[0];
+        let script = parse_single(
+            r#"
+            [[entries]]
+            name = "Build"
+            script = "cd $1\ncargo build"
+            "#,
+        );
+        assert_eq!(lines.script, "cd $1\ncargo build");
+        assert_eq!(lines.generate_id(), script.generate_id());
+
+        let both = "[[entries]]\nname = \"A\"\nscript = \"a\"\nscript_lines = [\"a\"]";
+        assert!(CommandsSource::from_toml(both).is_err());
+        let invalid = "[[entries]]\nname = \"A\"\nscript_lines = [1]";
+        assert!(CommandsSource::from_toml(invalid).is_err());
+    }
+
+    #[test]
+    fn delete_entry_with_overrides() {
+        std::env::set_var("IFORGOR_TEST_DELETE_SITE", "office");
+
+        let content = r#"
+[defaults]
+shell = "sh"
+
+# Deploy to the site registry.
+[[entries]]
+name = "Deploy"
+script = "deploy home"
+
+[entries.overrides."env:IFORGOR_TEST_DELETE_SITE=office"]
+script = "deploy office"
+
+[[entries]]
+name = "Build"
+script = "build"
+
+[entries.overrides."host:not-this-host.invalid"]
+name = "Build elsewhere"
+"#;
+        let source = CommandsSource::from_toml(content).unwrap();
+        let deploy = source.entries[0].generate_id();
+        let build = source.entries[1].generate_id();
+
+        let remaining = remove_source_entry(content, &build).unwrap().unwrap();
+        let source = CommandsSource::from_toml(&remaining).unwrap();
+        assert_eq!(source.entries.len(), 1);
+        assert_eq!(source.entries[0].script, "deploy office");
+        assert!(remaining.contains("# Deploy to the site registry."));
+        assert!(remaining.contains("[entries.overrides.\"env:IFORGOR_TEST_DELETE_SITE=office\"]"));
+
+        // The id of the overridden script is found.
+        let remaining = remove_source_entry(&remaining, &deploy).unwrap().unwrap();
+        assert!(CommandsSource::from_toml(&remaining)
+            .unwrap()
+            .entries
+            .is_empty());
+
+        assert!(remove_source_entry(content, &"unknown".to_string())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn delete_entry_with_script_lines() {
+        let content = r#"
+[[entries]]
+name = "Release"
+script_lines = ["cargo test", "cargo publish"]
+
+[[entries]]
+name = "Check"
+script_lines = ["cargo check"]
+"#;
+        let source = CommandsSource::from_toml(content).unwrap();
+        let release = source.entries[0].generate_id();
+
+        let remaining = remove_source_entry(content, &release).unwrap().unwrap();
+        let source = CommandsSource::from_toml(&remaining).unwrap();
+        assert_eq!(source.entries.len(), 1);
+        assert_eq!(source.entries[0].name, "Check");
+        assert!(remaining.contains(r#"script_lines = ["cargo check"]"#));
+    }
+
+    #[test]
+    fn source_name_and_description() {
+        let source = parse_source(
+            r#"
+            name = "Work Deploy Scripts"
+            description = "Scripts to deploy stuff"
+
+            [[entries]]
+            name = "Deploy"
+            script = "deploy"
+            "#,
+        );
+
+        assert_eq!(source.name.as_deref(), Some("Work Deploy Scripts"));
+        assert_eq!(
+            source.description.as_deref(),
+            Some("Scripts to deploy stuff")
+        );
+        assert_eq!(source.entries.len(), 1);
+
+        let source = parse_source("entries = []");
+        assert!(source.name.is_none());
+        assert!(source.description.is_none());
+
+        // Reported as a source without commands when loading it.
+        let source = parse_source("[[entry]]\nname = \"Typo\"\nscript = \"true\"");
+        assert!(source.entries.is_empty());
+    }
+
+    #[test]
+    fn source_defaults() {
+        let source = CommandsSource::from_toml(
+            r#"
+            [defaults]
+            shell = "powershell"
+            tags = ["work"]
+
+            [[entries]]
+            name = "Default"
+            script = "a"
+
+            [[entries]]
+            name = "Override"
+            script = "b"
+            shell = "sh"
+            tags = []
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(source.entries[0].shell, Shell::Powershell);
+        assert_eq!(source.entries[0].tags, ["work"]);
+        assert_eq!(source.entries[1].shell, Shell::Sh);
+        assert!(source.entries[1].tags.is_empty());
+
+        // Defaults are kept as is when reading the source without applying
+        // them, which is used to modify source files.
+        let raw = parse_source(
+            "defaults = { risky = true }
+[[entries]]
+name = \"A\"\nscript = \"a\"",
+        );
+        assert!(!raw.entries[0].risky);
+        assert_eq!(raw.defaults.get("risky"), Some(&toml::Value::Boolean(true)));
+
+        assert!(CommandsSource::from_toml("defaults = 1\nentries = []").is_err());
+    }
+
+    #[test]
+    fn invalid_only_in_dir_hides_command() {
+        let command = |only_in_dir: &str| UserCommand {
+            only_in_dir: Some(only_in_dir.to_string()),
+            ..Default::default()
+        };
+        let dir = Path::new("/home/user/project");
+
+        assert!(filter_only_in_dir(dir, &command("**/project"), false));
+        assert!(!filter_only_in_dir(dir, &command("**/other"), false));
+        assert!(!filter_only_in_dir(dir, &command("[project"), false));
+    }
+
+    #[test]
+    fn only_in_dir_subdirs() {
+        let command = UserCommand {
+            only_in_dir: Some("/home/*/project".to_string()),
+            ..Default::default()
+        };
+
+        for (dir, subdirs, expected) in [
+            ("/home/user/project", false, true),
+            ("/home/user/project/src", false, false),
+            ("/home/user/project/src", true, true),
+            ("/home/user", true, false),
+        ] {
+            assert_eq!(
+                filter_only_in_dir(Path::new(dir), &command, subdirs),
+                expected,
+                "for {dir} with subdirs = {subdirs}"
+            );
+        }
+    }
+
+    #[test]
+    fn finds_duplicate_names() {
+        let mut commands = BTreeMap::new();
+        for (name, script, source) in [
+            ("deploy", "a", Some("/a/prod.toml")),
+            ("deploy", "b", Some("/b/staging.toml")),
+            ("deploy", "c", None),
+            ("build", "d", Some("/a/prod.toml")),
+        ] {
+            let mut command = parse_single(&format!(
+                "[[entries]]\nname = \"{name}\"\nscript = \"{script}\""
+            ));
+            command.source_path = source.map(PathBuf::from);
+            commands.insert(command.generate_id(), command);
+        }
+
+        let duplicates = duplicate_names(&commands);
+        assert_eq!(duplicates.len(), 1);
+
+        let mut sources = duplicates["deploy"].clone();
+        sources.sort();
+        assert_eq!(sources, ["env", "prod.toml", "staging.toml"]);
+    }
+
+    #[test]
+    fn human_durations() {
+        assert_eq!(format_duration(Duration::from_millis(4230)), "4.2s");
+        assert_eq!(format_duration(Duration::from_secs(133)), "2m13s");
+        assert_eq!(format_duration(Duration::from_secs(3605)), "1h00m05s");
+    }
+
+    #[test]
+    fn last_run_summary() {
+        let run = |success| LastRun {
+            timestamp: 1000,
+            success,
+        };
+
+        assert_eq!(run(Some(true)).summary(1005), "✓ 5s ago");
+        assert_eq!(run(Some(false)).summary(1000 + 150), "✗ 2m ago");
+        assert_eq!(run(None).summary(1000 + 7200), "2h ago");
+        assert_eq!(run(None).summary(1000 + 3 * 86400), "3d ago");
+        assert_eq!(run(None).summary(0), "0s ago");
+    }
+
+    fn history_of(ids: &[&str]) -> History {
+        let mut history = History::default();
+        for id in ids {
+            history.add_entry(&id.to_string());
+        }
+        history
+    }
+
+    #[test]
+    fn history_adds_new_entry() {
+        let mut history = History::default();
+        history.add_entry(&"a".to_string());
+        assert_eq!(history.history, ["a"]);
+    }
+
+    #[test]
+    fn history_keeps_adding_order() {
+        let history = history_of(&["a", "b", "c"]);
+        assert_eq!(history.history, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn history_moves_readded_entry_to_end() {
+        let mut history = history_of(&["a", "b", "c"]);
+
+        history.add_entry(&"a".to_string());
+        assert_eq!(history.history, ["b", "c", "a"]);
+
+        // Re-adding the most recent entry doesn't duplicate it.
+        history.add_entry(&"a".to_string());
+        assert_eq!(history.history, ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn history_run_is_recorded_once() {
+        let mut history = history_of(&["a", "b"]);
+        history.add_run(&"a".to_string(), Some(true));
+
+        assert_eq!(history.history, ["b", "a"]);
+        assert_eq!(history.last_runs.len(), 1);
+        assert_eq!(history.last_runs["a"].success, Some(true));
+    }
+
+    #[test]
+    fn history_prunes_runs_of_removed_entries() {
+        let mut history = history_of(&["a", "b"]);
+        history.add_run(&"a".to_string(), Some(true));
+        history.add_run(&"b".to_string(), Some(false));
+        history.history.retain(|id| id != "a");
+
+        history.add_run(&"c".to_string(), None);
+        assert_eq!(history.history, ["b", "c"]);
+        assert_eq!(history.last_runs.keys().collect::<Vec<_>>(), ["b", "c"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn retries_failures_but_not_signals() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let success = process::ExitStatus::from_raw(0);
+        let failure = process::ExitStatus::from_raw(1 << 8);
+        // Killed by SIGINT.
+        let signal = process::ExitStatus::from_raw(2);
+
+        assert!(should_retry(failure, 1, 2));
+        assert!(should_retry(failure, 2, 2));
+        assert!(!should_retry(failure, 3, 2));
+        assert!(!should_retry(failure, 1, 0));
+        assert!(!should_retry(success, 1, 2));
+        assert!(!should_retry(signal, 1, 2));
+    }
+
+    #[test]
+    fn variadic_values_are_not_remembered() {
+        let mut command = parse_single(
+            r#"
+            [[entries]]
+            name = "Copy"
+            script = "cp -t $1 $2"
+            args = ["Destination", "Files"]
+            variadic = true
+            "#,
+        );
+
+        let values = ["dest", "a", "b"].map(String::from).to_vec();
+        command.remember_args(values.clone());
+        assert_eq!(command.args_default, ["dest"]);
+
+        command.variadic = false;
+        command.remember_args(values);
+        assert_eq!(command.args_default, ["dest", "a", "b"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn porcelain_outcome() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let outcome = RunOutcome {
+            status: Some(process::ExitStatus::from_raw(0)),
+            duration: Duration::from_millis(1234),
+        };
+        assert_eq!(
+            outcome.porcelain("abc", "Build all"),
+            "id=abc name='Build all' status=exited exit_code=0 duration_ms=1234"
+        );
+
+        let outcome = RunOutcome {
+            status: None,
+            duration: Duration::ZERO,
+        };
+        assert_eq!(
+            outcome.porcelain("abc", "Serve"),
+            "id=abc name=Serve status=detached exit_code= duration_ms=0"
+        );
+    }
 
-    pattern.matches_path(&current_dir)
+    #[test]
+    fn round_trip() {
+        let source = parse_source(
+            r#"
+            [[entries]]
+            name = "Minimal"
+            script = "echo minimal"
+
+            [[entries]]
+            name = "Full"
+            script = "Write-Host $args[0]"
+            args = ["Name"]
+            args_default = ["World"]
+            only_on = "windows"
+            shell = "powershell"
+            only_in_dir = "**/project"
+            risky = true
+            requires = ["git"]
+            show_if = "git status"
+            "#,
+        );
+
+        let serialized = toml::to_string(&source).expect("source to serialize");
+        let decoded = parse_source(&serialized);
+
+        assert_eq!(
+            toml::to_string(&decoded).expect("source to serialize"),
+            serialized
+        );
+
+        let full = &decoded.entries[1];
+        assert!(matches!(full.only_on, Some(Platform::Windows)));
+        assert_eq!(full.shell, Shell::Powershell);
+        assert!(full.risky);
+    }
 }
@@ -1,18 +1,25 @@
 use {
     anyhow::anyhow,
-    serde::{de::DeserializeOwned, Serialize},
+    serde::{
+        de::{DeserializeOwned, IntoDeserializer},
+        Deserialize, Serialize,
+    },
     std::{
         fs::File,
         io::{Read, Write},
         ops::{Deref, DerefMut},
-        path::PathBuf,
+        path::{Path, PathBuf},
     },
+    toml_edit::{ArrayOfTables, DocumentMut, Item, Table, Value},
 };
 
 #[derive(Debug)]
 pub struct OnDisk<T> {
     inner: T,
     path: PathBuf,
+    /// Document as it was read from disk, used to keep the comments and
+    /// formatting of the file when saving.
+    document: Option<DocumentMut>,
 }
 
 impl<T: Serialize + DeserializeOwned + Default> OnDisk<T> {
@@ -22,17 +29,18 @@ impl<T: Serialize + DeserializeOwned + Default> OnDisk<T> {
         file.read_to_string(&mut content)?;
         let inner = toml::from_str(&content)?;
 
-        Ok(Self { path, inner })
+        Ok(Self {
+            path,
+            inner,
+            document: content.parse().ok(),
+        })
     }
 
     pub fn open_or_default(path: PathBuf) -> anyhow::Result<Self> {
         let mut file = match File::open(&path) {
             Ok(file) => file,
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
-                return Ok(Self {
-                    path,
-                    inner: Default::default(),
-                })
+                return Ok(Self::new_from_default(path))
             }
             Err(e) => Err(e)?,
         };
@@ -41,21 +49,23 @@ impl<T: Serialize + DeserializeOwned + Default> OnDisk<T> {
         file.read_to_string(&mut content)?;
 
         if content.trim().is_empty() {
-            return Ok(Self {
-                path,
-                inner: Default::default(),
-            });
+            return Ok(Self::new_from_default(path));
         }
 
         let inner = toml::from_str(&content)?;
 
-        Ok(Self { path, inner })
+        Ok(Self {
+            path,
+            inner,
+            document: content.parse().ok(),
+        })
     }
 
     pub fn new_from_default(path: PathBuf) -> Self {
         Self {
             path,
             inner: Default::default(),
+            document: None,
         }
     }
 
@@ -66,8 +76,20 @@ impl<T: Serialize + DeserializeOwned + Default> OnDisk<T> {
             .ok_or(anyhow!("expected file to be in a folder"))?;
         std::fs::create_dir_all(folder)?;
 
-        let mut file = File::create(&self.path)?;
         let content = toml::to_string(&self.inner)?;
+        let content = match &self.document {
+            // Only apply the changes to the original document, which keeps
+            // the comments and formatting of untouched parts.
+            Some(document) => {
+                let mut document = document.clone();
+                let new_document: DocumentMut = content.parse()?;
+                merge_table(document.as_table_mut(), new_document.as_table());
+                document.to_string()
+            }
+            None => content,
+        };
+
+        let mut file = File::create(&self.path)?;
         file.write_all(content.as_bytes())?;
 
         Ok(())
@@ -91,3 +113,281 @@ impl<T> DerefMut for OnDisk<T> {
         &mut self.inner
     }
 }
+
+/// Number of backups kept by [`backup_file`].
+const BACKUP_COUNT: usize = 3;
+
+/// Copy a file to `<file>.bak` before a destructive operation. Previous
+/// backups are rotated to `<file>.bak.1`, `<file>.bak.2`, etc, and the oldest
+/// one is deleted. Returns the path of the backup, or `None` if the file
+/// doesn't exist.
+pub fn backup_file(path: &Path) -> anyhow::Result<Option<PathBuf>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let backup_path = |i: usize| {
+        let mut name = path.as_os_str().to_owned();
+        match i {
+            0 => name.push(".bak"),
+            i => name.push(format!(".bak.{i}")),
+        }
+        PathBuf::from(name)
+    };
+
+    for i in (1..BACKUP_COUNT).rev() {
+        let older = backup_path(i - 1);
+        if older.exists() {
+            std::fs::rename(older, backup_path(i))?;
+        }
+    }
+
+    let backup = backup_path(0);
+    std::fs::copy(path, &backup)?;
+
+    Ok(Some(backup))
+}
+
+/// Update `target` to have the same content as `source`, while keeping the
+/// decor (comments, whitespaces) of items that still exist.
+fn merge_table(target: &mut Table, source: &Table) {
+    target.retain(|key, _| source.contains_key(key));
+
+    for (key, source_item) in source.iter() {
+        match target.get_mut(key) {
+            Some(target_item) => merge_item(target_item, source_item),
+            None => {
+                target.insert(key, new_item(source_item));
+            }
+        }
+    }
+}
+
+fn merge_item(target: &mut Item, source: &Item) {
+    match (target, source) {
+        (Item::Table(target), Item::Table(source)) => {
+            merge_table(target, source);
+
+            // Implicit tables (only containing tables) are not written once
+            // empty, which would remove the key.
+            if target.is_empty() {
+                target.set_implicit(false);
+            }
+        }
+        (Item::ArrayOfTables(target), Item::ArrayOfTables(source)) => {
+            merge_array_of_tables(target, source)
+        }
+        (Item::Value(target), Item::Value(source)) => {
+            if !same_value(target, source) {
+                let decor = target.decor().clone();
+                *target = source.clone();
+                *target.decor_mut() = decor;
+            }
+        }
+        (target, source) => *target = new_item(source),
+    }
+}
+
+/// Tables are matched in order. When `source` has less tables, the tables of
+/// `target` that don't match are removed first, so that removing a table in
+/// the middle keeps the formatting of the next ones.
+fn merge_array_of_tables(target: &mut ArrayOfTables, source: &ArrayOfTables) {
+    for (i, source_table) in source.iter().enumerate() {
+        loop {
+            let extra_tables = target.len().saturating_sub(source.len());
+
+            match target.get_mut(i) {
+                None => target.push(new_table(source_table)),
+                Some(target_table) if same_table(target_table, source_table) => (),
+                Some(_) if extra_tables > 0 => {
+                    target.remove(i);
+                    continue;
+                }
+                Some(target_table) => merge_table(target_table, source_table),
+            }
+
+            break;
+        }
+    }
+
+    while target.len() > source.len() {
+        target.remove(target.len() - 1);
+    }
+}
+
+/// Copy of an item from another document. Tables don't keep their position
+/// in the other document, so that they are written after their siblings.
+fn new_item(source: &Item) -> Item {
+    match source {
+        Item::Table(source) => Item::Table(new_table(source)),
+        Item::ArrayOfTables(source) => {
+            let mut array = ArrayOfTables::new();
+            for table in source.iter() {
+                array.push(new_table(table));
+            }
+            Item::ArrayOfTables(array)
+        }
+        source => source.clone(),
+    }
+}
+
+fn new_table(source: &Table) -> Table {
+    let mut table = Table::new();
+    table.set_implicit(source.is_implicit());
+    table.set_dotted(source.is_dotted());
+    merge_table(&mut table, source);
+    table
+}
+
+fn same_table(a: &Table, b: &Table) -> bool {
+    same_value(
+        &Value::InlineTable(a.clone().into_inline_table()),
+        &Value::InlineTable(b.clone().into_inline_table()),
+    )
+}
+
+/// Compare values ignoring their formatting.
+fn same_value(a: &Value, b: &Value) -> bool {
+    let parse = |value: &Value| toml::Value::deserialize(value.clone().into_deserializer()).ok();
+
+    match (parse(a), parse(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::collections::BTreeMap};
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Example {
+        values: Vec<String>,
+        tables: BTreeMap<String, BTreeMap<String, String>>,
+    }
+
+    #[derive(Debug, Default, Serialize, Deserialize)]
+    struct Entries {
+        entries: Vec<BTreeMap<String, String>>,
+    }
+
+    #[test]
+    fn removing_table_keeps_next_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.toml");
+        std::fs::write(
+            &path,
+            r#"[[entries]]
+name = "a"
+
+# Removed
+[[entries]]
+name = "b"
+
+# Kept
+[[entries]]
+name = 'c' # note
+"#,
+        )
+        .unwrap();
+
+        let mut file = OnDisk::<Entries>::open(path.clone()).unwrap();
+        file.entries.remove(1);
+        file.save().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"[[entries]]
+name = "a"
+
+# Kept
+[[entries]]
+name = 'c' # note
+"#
+        );
+    }
+
+    #[test]
+    fn backups_are_rotated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.toml");
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).ok();
+
+        assert_eq!(backup_file(&path).unwrap(), None);
+
+        for i in 0..5 {
+            std::fs::write(&path, i.to_string()).unwrap();
+            assert_eq!(
+                backup_file(&path).unwrap(),
+                Some(dir.path().join("file.toml.bak"))
+            );
+        }
+
+        assert_eq!(read("file.toml.bak").as_deref(), Some("4"));
+        assert_eq!(read("file.toml.bak.1").as_deref(), Some("3"));
+        assert_eq!(read("file.toml.bak.2").as_deref(), Some("2"));
+        assert_eq!(read("file.toml.bak.3"), None);
+    }
+
+    #[test]
+    fn emptied_table_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.toml");
+        std::fs::write(&path, "values = []\n\n[tables.one]\nkey = \"value\"\n").unwrap();
+
+        let mut file = OnDisk::<Example>::open(path.clone()).unwrap();
+        file.tables.clear();
+        file.save().unwrap();
+
+        let file = OnDisk::<Example>::open(path.clone()).unwrap();
+        assert!(file.tables.is_empty());
+    }
+
+    #[test]
+    fn save_keeps_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.toml");
+        std::fs::write(
+            &path,
+            r#"# Header comment
+values = [
+    "a", # first
+    "b",
+]
+
+# Some notes
+[tables.one]
+key = 'literal' # inline note
+
+[tables.two]
+key = "removed"
+"#,
+        )
+        .unwrap();
+
+        let mut file = OnDisk::<Example>::open(path.clone()).unwrap();
+        file.tables.remove("two");
+        file.tables.insert(
+            "three".to_string(),
+            [("k".to_string(), "v".to_string())].into(),
+        );
+        file.save().unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"# Header comment
+values = [
+    "a", # first
+    "b",
+]
+
+# Some notes
+[tables.one]
+key = 'literal' # inline note
+
+[tables.three]
+k = "v"
+"#
+        );
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    crate::{refuse_nested_menu, wait_for_enter, OnDisk, Registry},
+    ichoose::{ListEntry, ListSearch, ListSearchExtra},
+    std::path::PathBuf,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Action {
+    Add,
+    Remove,
+    Reload,
+}
+
+/// Interactive menu to manage sources.
+pub fn run(registry: &mut OnDisk<Registry>) -> anyhow::Result<()> {
+    refuse_nested_menu()?;
+
+    loop {
+        let actions = [
+            (Action::Add, "Add sources from the current directory"),
+            (Action::Remove, "Remove sources"),
+            (Action::Reload, "Reload sources"),
+        ]
+        .map(|(key, name)| ListEntry::new(key, name))
+        .to_vec();
+
+        let text = if registry.sources.is_empty() {
+            "No registered source.".to_string()
+        } else {
+            let sources: Vec<_> = registry
+                .sources
+                .iter()
+                .map(|source| format!("- {}", source.display()))
+                .collect();
+            format!("Registered sources:\n{}", sources.join("\n"))
+        };
+
+        let Some(action) = ListSearch {
+            items: actions,
+            extra: ListSearchExtra {
+                title: " iforgor sources ".to_string(),
+                text,
+                ..Default::default()
+            },
+        }
+        .run()?
+        .pop_first() else {
+            return Ok(());
+        };
+
+        let res = match action {
+            Action::Add => add_sources(registry),
+            Action::Remove => remove_sources(registry),
+            Action::Reload => registry.reload(),
+        };
+
+        match res {
+            Ok(()) => registry.save()?,
+            Err(e) => println!("Error: {e}"),
+        }
+
+        print!("\nPress Enter to proceed.");
+        wait_for_enter()?;
+    }
+}
+
+/// Pick `.toml` files of the current directory to add as sources.
+pub fn add_sources(registry: &mut Registry) -> anyhow::Result<()> {
+    let current_dir = std::env::current_dir()?;
+
+    let mut candidates: Vec<_> = std::fs::read_dir(&current_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "toml"))
+        .filter(|path| {
+            std::fs::canonicalize(path).is_ok_and(|path| !registry.sources.contains(&path))
+        })
+        .map(|path| {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ListEntry::new(path, name)
+        })
+        .collect();
+    candidates.sort();
+
+    if candidates.is_empty() {
+        println!(
+            "No unregistered `.toml` file in \"{}\".",
+            current_dir.display()
+        );
+        return Ok(());
+    }
+
+    refuse_nested_menu()?;
+    let paths = ListSearch {
+        items: candidates,
+        extra: ListSearchExtra {
+            title: " Select sources to add ".to_string(),
+            text: format!("TOML files in \"{}\".", current_dir.display()),
+            multi_select: true,
+            ..Default::default()
+        },
+    }
+    .run()?;
+
+    for path in paths {
+        registry.add_source(path)?;
+    }
+
+    Ok(())
+}
+
+/// Pick registered sources to remove, then reload commands.
+pub fn remove_sources(registry: &mut Registry) -> anyhow::Result<()> {
+    let sources: Vec<ListEntry<PathBuf>> = registry
+        .sources
+        .iter()
+        .map(|source| ListEntry::new(source.clone(), source.display().to_string()))
+        .collect();
+
+    refuse_nested_menu()?;
+    let paths = ListSearch {
+        items: sources,
+        extra: ListSearchExtra {
+            title: " Select sources to remove ".to_string(),
+            multi_select: true,
+            ..Default::default()
+        },
+    }
+    .run()?;
+
+    if paths.is_empty() {
+        return Ok(());
+    }
+
+    for path in paths {
+        registry.remove_source(path)?;
+    }
+
+    registry.reload()
+}